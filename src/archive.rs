@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom};
+
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+use super::errors::{Error, XlsxFormatError};
+use super::parse_xml;
+use super::structs::{Relationship, RelationshipKind, SpreadsheetFormat};
+
+/// every zip archive starts with a local file header: `PK\x03\x04`
+const ZIP_SIGNATURE: [u8; 4] = *b"PK\x03\x04";
+
+const CONTENT_TYPES_XML: &str = "[Content_Types].xml";
+/// where the workbook is when `_rels/.rels` doesn't say
+const DEFAULT_WORKBOOK_XML: &str = "xl/workbook.xml";
+
+/// the most a read pre-allocates, the uncompressed size in a zip header is
+/// declared by whoever wrote the file and can't be trusted
+const MAX_SIZE_HINT: usize = 16 * 1024 * 1024;
+
+/// an opened xlsx package, its parts are read straight from the zip archive
+///
+/// nothing is written to disk, each part is decompressed only when asked for
+pub struct XlsxArchive<R> {
+    zip: ZipArchive<R>,
+    /// lowercased part name -> the part's name in the zip, part names are
+    /// compared case-insensitively
+    part_names: HashMap<String, String>,
+    /// the part the package's officeDocument relationship points at
+    workbook_part_name: String,
+    format: SpreadsheetFormat,
+}
+
+impl<R> fmt::Debug for XlsxArchive<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("XlsxArchive").finish_non_exhaustive()
+    }
+}
+
+impl<R: Read + Seek> XlsxArchive<R> {
+    /// open a zip archive and check it is a spreadsheet package
+    ///
+    /// the format is detected by the zip signature and the content type of
+    /// the workbook, the part `_rels/.rels` names as the officeDocument. the
+    /// file extension plays no part
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let mut signature = [0u8; 4];
+        reader.seek(SeekFrom::Start(0))?;
+        if reader.read_exact(&mut signature).is_err()
+            || signature != ZIP_SIGNATURE
+        {
+            return Err(XlsxFormatError::NotZip.into());
+        }
+        reader.seek(SeekFrom::Start(0))?;
+
+        let zip = ZipArchive::new(reader)?;
+        let part_names = zip
+            .file_names()
+            .map(|name| (name.to_lowercase(), name.to_owned()))
+            .collect();
+        let mut archive = XlsxArchive {
+            zip,
+            part_names,
+            workbook_part_name: DEFAULT_WORKBOOK_XML.to_owned(),
+            format: SpreadsheetFormat::Xlsx,
+        };
+        // the package's own relationships, the part they belong to is the
+        // package root
+        if let Some(workbook_part_name) = archive
+            .read_relationships("")?
+            .into_values()
+            .find(|rel| rel.kind == RelationshipKind::OfficeDocument)
+            .and_then(|rel| rel.part_name)
+        {
+            archive.workbook_part_name = workbook_part_name;
+        }
+        let content_types_xml = archive
+            .read_part_string(CONTENT_TYPES_XML)?
+            .ok_or(XlsxFormatError::MissingContentTypes)?;
+        let content_types = parse_xml::get_content_types(
+            &content_types_xml,
+            CONTENT_TYPES_XML,
+        )?;
+        archive.format = content_types
+            .of_part(&archive.workbook_part_name)
+            .and_then(SpreadsheetFormat::from_content_type)
+            .ok_or(XlsxFormatError::NotSpreadsheet)?;
+
+        Ok(archive)
+    }
+
+    /// the name a part has in the zip, matched case-insensitively, a
+    /// leading `/` is ignored. `None` if the part does not exist
+    pub fn find_part_name(&self, part_name: &str) -> Option<&str> {
+        let part_name = part_name.strip_prefix('/').unwrap_or(part_name);
+        self.part_names.get(&part_name.to_lowercase()).map(String::as_str)
+    }
+
+    /// the workbook part, e.g. `xl/workbook.xml`
+    pub fn workbook_part_name(&self) -> &str {
+        &self.workbook_part_name
+    }
+
+    /// the package variant, from the workbook content type
+    pub fn format(&self) -> SpreadsheetFormat {
+        self.format
+    }
+
+    /// check if a part exists, e.g. `xl/workbook.xml`
+    pub fn has_part(&self, part_name: &str) -> bool {
+        self.find_part_name(part_name).is_some()
+    }
+
+    /// the relationships of a part, from its .rels part, empty if it has
+    /// none. internal targets are resolved against the part and matched to
+    /// the zip entry they name, a target naming no entry gets no part name
+    pub fn read_relationships(
+        &mut self,
+        part_name: &str,
+    ) -> Result<HashMap<String, Relationship>, Error> {
+        let rels_part_name = parse_xml::get_rels_part_name(part_name);
+        let Some(rels_xml) = self.read_part_string(&rels_part_name)? else {
+            return Ok(HashMap::new());
+        };
+        let mut relationships =
+            parse_xml::get_relationships(&rels_xml, &rels_part_name)?;
+        // a target naming no part in the package is dangling, it's kept in
+        // the graph but leads nowhere
+        for rel in relationships.values_mut() {
+            rel.part_name = rel
+                .part_name
+                .as_deref()
+                .and_then(|name| self.find_part_name(name))
+                .map(str::to_owned);
+        }
+        Ok(relationships)
+    }
+
+    /// read a part as raw bytes, `None` if the part does not exist, the part
+    /// name is matched case-insensitively
+    pub fn read_part_bytes(
+        &mut self,
+        part_name: &str,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let Some(part_name) =
+            self.find_part_name(part_name).map(str::to_owned)
+        else {
+            return Ok(None);
+        };
+        let mut file = match self.zip.by_name(&part_name) {
+            Ok(file) => file,
+            Err(ZipError::FileNotFound) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let size_hint = usize::try_from(file.size()).unwrap_or(usize::MAX);
+        let mut buf = Vec::with_capacity(size_hint.min(MAX_SIZE_HINT));
+        file.read_to_end(&mut buf)?;
+        Ok(Some(buf))
+    }
+
+    /// read a part as utf-8 text, `None` if the part does not exist, the
+    /// part name is matched case-insensitively
+    pub fn read_part_string(
+        &mut self,
+        part_name: &str,
+    ) -> Result<Option<String>, Error> {
+        let Some(bytes) = self.read_part_bytes(part_name)? else {
+            return Ok(None);
+        };
+        let text = String::from_utf8(bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Some(text))
+    }
+
+    /// access the underlying zip archive, used when unzipping to disk
+    pub fn zip_mut(&mut self) -> &mut ZipArchive<R> {
+        &mut self.zip
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::{FileOptions, ZipWriter};
+
+    const REL: &str =
+        "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+
+    pub(crate) const WORKBOOK: &str =
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml";
+    const TEMPLATE: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.template.main+xml";
+
+    /// the bytes of a zip archive holding each (part name, content)
+    pub(crate) fn package_bytes(parts: &[(&str, &str)]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in parts {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn package(parts: &[(&str, &str)]) -> XlsxArchive<Cursor<Vec<u8>>> {
+        XlsxArchive::new(Cursor::new(package_bytes(parts))).unwrap()
+    }
+
+    /// a .rels part of (id, type without the namespace, target)
+    pub(crate) fn rels(relationships: &[(&str, &str, &str)]) -> String {
+        let relationships: String = relationships
+            .iter()
+            .map(|(id, kind, target)| {
+                format!(
+                    r#"<Relationship Id="{id}" Type="{REL}/{kind}" Target="{target}"/>"#
+                )
+            })
+            .collect();
+        format!(
+            r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">{relationships}</Relationships>"#
+        )
+    }
+
+    /// a `[Content_Types].xml` of (part name, content type) overrides
+    pub(crate) fn content_types(overrides: &[(&str, &str)]) -> String {
+        let overrides: String = overrides
+            .iter()
+            .map(|(part_name, content_type)| {
+                format!(
+                    r#"<Override PartName="{part_name}" ContentType="{content_type}"/>"#
+                )
+            })
+            .collect();
+        format!(
+            r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">{overrides}</Types>"#
+        )
+    }
+
+    #[test]
+    fn relationship_targets_match_zip_entries() {
+        let drawing_rels = rels(&[
+            ("rId1", "image", "../Media/IMAGE1.PNG"),
+            ("rId2", "image", "../media/image%202.png"),
+            ("rId3", "image", "/xl/media/image1.png"),
+            ("rId4", "image", "../media/missing.png"),
+        ]);
+        let mut archive = package(&[
+            (
+                CONTENT_TYPES_XML,
+                &content_types(&[("/xl/workbook.xml", WORKBOOK)]),
+            ),
+            ("xl/workbook.xml", "<workbook/>"),
+            ("xl/drawings/_rels/drawing1.xml.rels", &drawing_rels),
+            ("xl/media/image1.png", ""),
+            ("xl/media/image 2.png", ""),
+        ]);
+        let relationships =
+            archive.read_relationships("xl/drawings/drawing1.xml").unwrap();
+        let part_name = |r_id: &str| relationships[r_id].part_name.as_deref();
+        assert_eq!(part_name("rId1"), Some("xl/media/image1.png"));
+        assert_eq!(part_name("rId2"), Some("xl/media/image 2.png"));
+        assert_eq!(part_name("rId3"), Some("xl/media/image1.png"));
+        // dangling, kept in the graph without a part name
+        assert_eq!(part_name("rId4"), None);
+        assert_eq!(relationships["rId4"].target, "../media/missing.png");
+    }
+
+    #[test]
+    fn workbook_from_office_document_relationship() {
+        let mut archive = package(&[
+            (
+                CONTENT_TYPES_XML,
+                &content_types(&[
+                    ("/xl/other.xml", TEMPLATE),
+                    ("/xl/book.xml", WORKBOOK),
+                ]),
+            ),
+            (
+                "_rels/.rels",
+                &rels(&[("rId1", "officeDocument", "xl/book.xml")]),
+            ),
+            ("xl/book.xml", "<workbook/>"),
+        ]);
+        assert_eq!(archive.workbook_part_name(), "xl/book.xml");
+        assert_eq!(archive.format(), SpreadsheetFormat::Xlsx);
+        assert!(archive.read_part_string("/XL/BOOK.XML").unwrap().is_some());
+    }
+
+    #[test]
+    fn workbook_content_type_from_default() {
+        // no override, the extension's default gives the workbook its type
+        let content_types = format!(
+            r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="XML" ContentType="{TEMPLATE}"/></Types>"#
+        );
+        let archive = package(&[
+            (CONTENT_TYPES_XML, &content_types),
+            ("xl/workbook.xml", "<workbook/>"),
+        ]);
+        assert_eq!(archive.workbook_part_name(), "xl/workbook.xml");
+        assert_eq!(archive.format(), SpreadsheetFormat::Xltx);
+    }
+
+    #[test]
+    fn workbook_content_type_is_case_insensitive() {
+        let archive = package(&[
+            (
+                CONTENT_TYPES_XML,
+                &content_types(&[(
+                    "/XL/Workbook.xml",
+                    &WORKBOOK.to_uppercase(),
+                )]),
+            ),
+            ("xl/workbook.xml", "<workbook/>"),
+        ]);
+        assert_eq!(archive.format(), SpreadsheetFormat::Xlsx);
+    }
+
+    #[test]
+    fn main_part_not_a_workbook() {
+        let bytes = package_bytes(&[
+            (
+                CONTENT_TYPES_XML,
+                &content_types(&[
+                    ("/xl/workbook.xml", WORKBOOK),
+                    (
+                        "/word/document.xml",
+                        "application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml",
+                    ),
+                ]),
+            ),
+            (
+                "_rels/.rels",
+                &rels(&[("rId1", "officeDocument", "word/document.xml")]),
+            ),
+            ("word/document.xml", "<document/>"),
+            ("xl/workbook.xml", "<workbook/>"),
+        ]);
+        assert!(matches!(
+            XlsxArchive::new(Cursor::new(bytes)),
+            Err(Error::XlsxFormat(XlsxFormatError::NotSpreadsheet))
+        ));
+    }
+}
@@ -1,14 +1,39 @@
 use thiserror::Error;
 
+/// the error type of this lib, unifies every error below
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] IoError),
+    #[error(transparent)]
+    XlsxPath(#[from] XlsxPathParseError),
+    #[error(transparent)]
+    XlsxFormat(#[from] XlsxFormatError),
+    #[error(transparent)]
+    Zip(#[from] zip::result::ZipError),
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error("linked image {0} is outside the package and was not resolved")]
+    UnresolvedLink(String),
+    #[cfg(feature = "transform")]
+    #[error(transparent)]
+    Image(#[from] image::ImageError),
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e.into())
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum XlsxPathParseError {
-    #[error("invalid format. (expected {expected:?}, found {found:?})")]
-    InvalidFormat { expected: String, found: String },
     #[error("path provided is not a file: {0}")]
     FileNotFound(String),
 }
 
 #[derive(Error, Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum IoError {
     #[error("CreateTempDirError: Failed to create temp dir.")]
     CreateTempDirError {
@@ -16,7 +41,47 @@ pub enum IoError {
         #[source]
         source: std::io::Error,
     },
+    #[error("CanonicalizeError: {msg}")]
+    CanonicalizeError {
+        msg: String,
+        #[source]
+        source: std::io::Error,
+    },
     #[error(transparent)]
     UnzipXlsxError(#[from] std::io::Error),
 }
 
+#[derive(Error, Debug)]
+pub enum XlsxFormatError {
+    #[error("not a zip archive: missing zip signature")]
+    NotZip,
+    #[error("not an ooxml package: missing [Content_Types].xml")]
+    MissingContentTypes,
+    #[error("not a spreadsheet: the package's main part has no workbook content type in [Content_Types].xml")]
+    NotSpreadsheet,
+    #[error("not a valid workbook: required part {0} is missing")]
+    MissingPart(String),
+}
+
+/// a part of the xlsx package could not be parsed
+///
+/// **part**: the part name, e.g. `xl/drawings/drawing1.xml`
+///
+/// **element**: the local name of the offending element, e.g. `sheet`
+#[derive(Error, Debug)]
+pub enum ParseError {
+    #[error("{part}: malformed xml: {source}")]
+    MalformedXml {
+        part: String,
+        #[source]
+        source: roxmltree::Error,
+    },
+    #[error("{part}: <{element}> is missing attribute `{attribute}`")]
+    MissingAttribute { part: String, element: String, attribute: String },
+    #[error("{part}: <{element}> is missing <{child}>")]
+    MissingElement { part: String, element: String, child: String },
+    #[error("{part}: <{element}> has an invalid value {value:?}")]
+    InvalidValue { part: String, element: String, value: String },
+    #[error("{part}: part not found in the archive")]
+    MissingPart { part: String },
+}
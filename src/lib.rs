@@ -1,14 +1,31 @@
-//! retrieve {sheetname: {(col, row): imgpath}} for an xlsx file
+//! retrieve {sheetname: {(col, row): images}} for an xlsx file
 //!
-//! given an xlsx filepath, this lib copies it,
+//! given an xlsx filepath, a reader or bytes, this lib opens it as a zip
+//! archive,
 //!
-//! rename it to .zip file and unzip it, then parses .xml files contained,
+//! parses the .xml files contained straight from the archive,
 //!
-//! finally, retrieve a map of {sheetname: {(col, row): imgpath}}
+//! finally, retrieve a map of {sheetname: {(col, row): images}}
+//!
+//! image bytes are read lazily from the archive, nothing is unzipped to disk
+//! unless _ImgLoader::new_ is used
 
+mod archive;
 mod errors;
 mod parse_xml;
 mod structs;
+#[cfg(feature = "transform")]
+mod transform;
 mod unzip_utils;
 
-pub use structs::{ImgLoader, XlsxPath};
+pub use errors::{
+    Error, IoError, ParseError, XlsxFormatError, XlsxPathParseError,
+};
+pub use structs::{
+    AlternateContentPolicy, AnchorKind, AnchorMarker, BackgroundImage, Crop,
+    FillMode, GroupPosition, HeaderFooterImage, HeaderFooterKind,
+    HeaderFooterPage, HeaderFooterPosition, HeaderFooterSection, Hyperlink,
+    ImageAnchor, ImageKind, ImageProps, ImageSource, ImageTransform,
+    ImgLoader, LoadOptions, Relationship, RelationshipKind,
+    SheetRelationships, SpreadsheetFormat, XlsxImage, XlsxPath,
+};
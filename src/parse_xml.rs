@@ -1,382 +1,2620 @@
-// use anyhow::Error;
-use core::iter::IntoIterator;
-use roxmltree::{Document, Namespace, Node, NamespaceIter};
-use std::collections::HashMap;
-use std::fs;
+use roxmltree::{Document, ExpandedName, Node};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
-use std::iter::Iterator;
+use super::errors::{IoError, ParseError};
+use super::structs::{
+    AlternateContentPolicy, AnchorKind, AnchorMarker, Crop, FillMode,
+    GroupPosition, HeaderFooterImage, HeaderFooterKind, HeaderFooterPage,
+    HeaderFooterPosition, HeaderFooterSection, ImageAnchor, ImageKind,
+    ImageProps, ImageSource, ImageTransform, Relationship, RelationshipKind,
+    SheetRelationships, XlsxImage,
+};
 
-use super::errors::IoError;
+pub const EMU_PER_PIXEL: f64 = 9525.0;
+const EMU_PER_POINT: f64 = 12700.0;
+/// max digit width of the default font (Calibri 11) in pixels
+const MAX_DIGIT_WIDTH: f64 = 7.0;
+const DEFAULT_BASE_COL_WIDTH: f64 = 8.0;
+const DEFAULT_ROW_HEIGHT: f64 = 15.0;
+const MAX_COL: i64 = 16_384;
+const MAX_ROW: i64 = 1_048_576;
+
+const NS_MC: &str =
+    "http://schemas.openxmlformats.org/markup-compatibility/2006";
+/// `r:` attributes, transitional then strict (ISO 29500 Strict)
+const NS_R: [&str; 2] = [
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships",
+    "http://purl.oclc.org/ooxml/officeDocument/relationships",
+];
+/// `a:` elements, transitional then strict
+const NS_A: [&str; 2] = [
+    "http://schemas.openxmlformats.org/drawingml/2006/main",
+    "http://purl.oclc.org/ooxml/drawingml/main",
+];
+/// namespaces an `mc:Choice` may require for its branch to be read, the ones
+/// whose elements are understood when looking for images
+const SUPPORTED_NAMESPACES: &[&str] = &[
+    "http://schemas.openxmlformats.org/spreadsheetml/2006/main",
+    "http://schemas.openxmlformats.org/drawingml/2006/main",
+    "http://schemas.openxmlformats.org/drawingml/2006/spreadsheetDrawing",
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships",
+    "http://schemas.microsoft.com/office/drawing/2010/main",
+    "http://schemas.microsoft.com/office/spreadsheetml/2009/9/main",
+    "http://schemas.microsoft.com/office/drawing/2016/SVG/main",
+    "http://purl.oclc.org/ooxml/spreadsheetml/main",
+    "http://purl.oclc.org/ooxml/drawingml/main",
+    "http://purl.oclc.org/ooxml/drawingml/spreadsheetDrawing",
+    "http://purl.oclc.org/ooxml/officeDocument/relationships",
+];
+
+/// (col, row) of a cell, both 1-based
+pub type ColRow = (i64, i64);
 
 #[derive(Debug)]
 pub struct CellImgId {
-    col: Option<i64>,
-    row: Option<i64>,
     r_id: Option<String>,
+    /// `r:link` rather than `r:embed`
+    linked: bool,
+    anchor: ImageAnchor,
+    group_position: Option<GroupPosition>,
+    props: ImageProps,
+    hyperlink_r_id: Option<String>,
+    transform: ImageTransform,
+    svg_r_id: Option<String>,
+    kind: ImageKind,
+    /// the cell the image belongs to when it's not the anchor's top left
+    /// cell, e.g. the cell of a comment
+    cell: Option<ColRow>,
 }
 
-pub fn compute_abs_img_path(
-    relative_img_path: &Path,
-    media_dir: &Path,
-) -> PathBuf {
-    let basename = relative_img_path.file_name().unwrap();
-    let abs_img = media_dir.join(basename).canonicalize().unwrap();
-    // dbg!(&abs_img);
-    abs_img
-}
-
-fn get_namespace_str<'a>(
-    namespaces: &'a NamespaceIter<'_, '_>,
-    ident: &'a str,
-) -> Option<&'a str> {
-    let namespaces = namespaces.to_owned();
-    namespaces
-        .into_iter()
-        .find_map(|n| n.name().and_then(|k| (k == ident).then(|| n.uri())))
+/// an `a:xfrm` of a shape, with `chOff`/`chExt` when it's a group's
+#[derive(Debug, Clone, Copy, Default)]
+struct Xfrm {
+    off: (f64, f64),
+    ext: (f64, f64),
+    ch_off: (f64, f64),
+    ch_ext: (f64, f64),
 }
 
-fn get_node_with_tag_namespace<'a>(
-    parent_node: &'a Node,
-    namespace_str: &'a str,
-    tag_name: &'a str,
-) -> Option<Node<'a, 'a>> {
-    parent_node
-        .descendants()
-        .into_iter()
-        .find_map(|n| n.has_tag_name((namespace_str, tag_name)).then(|| n))
+impl Xfrm {
+    /// map a rect from this group's child coordinate space into its parent's
+    fn apply(&self, rect: (f64, f64, f64, f64)) -> (f64, f64, f64, f64) {
+        let scale_x = if self.ch_ext.0 != 0.0 {
+            self.ext.0 / self.ch_ext.0
+        } else {
+            1.0
+        };
+        let scale_y = if self.ch_ext.1 != 0.0 {
+            self.ext.1 / self.ch_ext.1
+        } else {
+            1.0
+        };
+        let (x, y, cx, cy) = rect;
+        (
+            self.off.0 + (x - self.ch_off.0) * scale_x,
+            self.off.1 + (y - self.ch_off.1) * scale_y,
+            cx * scale_x,
+            cy * scale_y,
+        )
+    }
 }
 
-fn get_node_with_attr_namespace<'a>(
-    node: &'a Node,
-    namespace_str: &'a str,
-    attr_name: &'a str,
-) -> Option<Node<'a, 'a>> {
-    node.descendants()
-        .into_iter()
-        .find_map(|n| n.has_attribute((namespace_str, attr_name)).then(|| n))
+/// column widths and row heights of a worksheet,
+/// used to find the cell covering a point given in EMU
+#[derive(Debug, Default)]
+pub struct SheetDimensions {
+    /// in characters
+    default_col_width: Option<f64>,
+    base_col_width: Option<f64>,
+    /// in points
+    default_row_height: Option<f64>,
+    /// (min col, max col, width in characters)
+    col_widths: Vec<(i64, i64, f64)>,
+    /// {row: height in points}
+    row_heights: HashMap<i64, f64>,
 }
 
-fn convert_node_text_to_i64(node: &Node) -> Option<i64> {
-    node.text()
-        .and_then(|txt| txt.parse::<i64>().ok())
-        .and_then(|num| Some(num))
-}
+impl SheetDimensions {
+    fn col_width_emu(&self, col: i64) -> f64 {
+        let width = self
+            .col_widths
+            .iter()
+            .find_map(|(min, max, width)| {
+                (*min..=*max).contains(&col).then_some(*width)
+            })
+            .or(self.default_col_width);
+        let pixels = match width {
+            Some(width) => {
+                ((256.0 * width + (128.0 / MAX_DIGIT_WIDTH).trunc()) / 256.0
+                    * MAX_DIGIT_WIDTH)
+                    .trunc()
+            }
+            // default width is the base width plus 5px padding,
+            // rounded up to a multiple of 8px
+            None => {
+                let base =
+                    self.base_col_width.unwrap_or(DEFAULT_BASE_COL_WIDTH);
+                ((base * MAX_DIGIT_WIDTH + 5.0) / 8.0).ceil() * 8.0
+            }
+        };
+        pixels * EMU_PER_PIXEL
+    }
 
-pub fn get_col_row_r_id(col_row_r_id_file: &Path) -> Vec<CellImgId> {
-    let mut entries: Vec<CellImgId> = Vec::new();
-    let file_str = std::fs::read_to_string(col_row_r_id_file).unwrap();
-    let doc = Document::parse(&file_str).unwrap();
-    let namespaces = doc.root_element().namespaces();
-
-    // let ns_default = Some("http://schemas.openxmlformats.org/package/2006/relationships");
-    // let xdr_str = "http://schemas.openxmlformats.org/drawingml/2006/spreadsheetDrawing";
-    // let ns_r = get_namespace_str(namespaces, "r");
-    let ns_xdr = get_namespace_str(&namespaces, "xdr");
-    let ns_a = get_namespace_str(&namespaces, "a");
-
-    let ns_r = Some(
-        "http://schemas.openxmlformats.org/officeDocument/2006/relationships",
-    );
+    fn row_height_emu(&self, row: i64) -> f64 {
+        let height = self
+            .row_heights
+            .get(&row)
+            .copied()
+            .or(self.default_row_height)
+            .unwrap_or(DEFAULT_ROW_HEIGHT);
+        height * EMU_PER_POINT
+    }
 
-    if ns_xdr.is_some() && ns_a.is_some() && ns_r.is_some() {
-        for two_cell_anchor in doc.descendants().into_iter().filter(|c| {
-            c.has_tag_name((ns_xdr.unwrap(), "twoCellAnchor"))
-            // c.lookup_namespace_uri(Some("xdr")).is_some()
-            //     && c.has_tag_name((ns_xdr.unwrap(), "twoCellAnchor"))
-        }) {
-            let mut col: Option<i64> = None;
-            let mut row: Option<i64> = None;
-            let mut r_id: Option<String> = None;
-
-            if let Some(from_node) = get_node_with_tag_namespace(
-                &two_cell_anchor,
-                ns_xdr.unwrap(),
-                "from",
-            ) {
-                if let Some(col_node) = get_node_with_tag_namespace(
-                    &from_node,
-                    ns_xdr.unwrap(),
-                    "col",
-                ) {
-                    col = convert_node_text_to_i64(&col_node)
-                        .and_then(|num| Some(num + 1));
-                }
-                if let Some(row_node) = get_node_with_tag_namespace(
-                    &from_node,
-                    ns_xdr.unwrap(),
-                    "row",
-                ) {
-                    row = convert_node_text_to_i64(&row_node)
-                        .and_then(|num| Some(num + 1));
-                }
+    /// the cell covering the point (x, y) given in EMU,
+    /// with the offset of the point into that cell
+    pub fn marker_at(&self, x: i64, y: i64) -> AnchorMarker {
+        let (x, y) = (x as f64, y as f64);
+        let mut col = 1;
+        let mut left = 0.0;
+        while col < MAX_COL {
+            let width = self.col_width_emu(col);
+            if left + width > x {
+                break;
             }
+            left += width;
+            col += 1;
+        }
+        let mut row = 1;
+        let mut top = 0.0;
+        while row < MAX_ROW {
+            let height = self.row_height_emu(row);
+            if top + height > y {
+                break;
+            }
+            top += height;
+            row += 1;
+        }
+        AnchorMarker {
+            col,
+            row,
+            col_off: (x - left).round() as i64,
+            row_off: (y - top).round() as i64,
+        }
+    }
 
-            if let Some(pic_node) = get_node_with_tag_namespace(
-                &two_cell_anchor,
-                ns_xdr.unwrap(),
-                "pic",
-            ) {
-                if let Some(blip_fill_node) = get_node_with_tag_namespace(
-                    &pic_node,
-                    ns_xdr.unwrap(),
-                    "blipFill",
-                ) {
-                    if let Some(blip_node) = get_node_with_tag_namespace(
-                        &blip_fill_node,
-                        ns_a.unwrap(),
-                        "blip",
-                    ) {
-                        if let Some(embed_ele) = get_node_with_attr_namespace(
-                            &blip_node,
-                            ns_r.unwrap(),
-                            "embed",
-                        ) {
-                            let mut attrs = embed_ele.attributes();
-                            r_id = Some(attrs.next().unwrap().value().to_owned());
-                            //dbg!(r_id);
-                        }
-                    }
-                }
+    /// the cell covering the point (x + dx, y + dy), `None` if it's out of
+    /// the range of an i64
+    pub fn marker_at_offset(
+        &self,
+        (x, y): (i64, i64),
+        (dx, dy): (i64, i64),
+    ) -> Option<AnchorMarker> {
+        Some(self.marker_at(x.checked_add(dx)?, y.checked_add(dy)?))
+    }
+
+    /// the point (x, y) in EMU a marker points at, `None` if it's out of
+    /// the range of an i64
+    pub fn position_of(&self, marker: &AnchorMarker) -> Option<(i64, i64)> {
+        let left: f64 = (1..marker.col.min(MAX_COL))
+            .map(|col| self.col_width_emu(col))
+            .sum();
+        let top: f64 = (1..marker.row.min(MAX_ROW))
+            .map(|row| self.row_height_emu(row))
+            .sum();
+        Some((
+            (left.round() as i64).checked_add(marker.col_off)?,
+            (top.round() as i64).checked_add(marker.row_off)?,
+        ))
+    }
+
+    /// the width and height in EMU between two markers, 0 where `to` comes
+    /// before `from`. `None` if it's out of the range of an i64
+    pub fn size_between(
+        &self,
+        from: &AnchorMarker,
+        to: &AnchorMarker,
+    ) -> Option<(i64, i64)> {
+        let (x1, y1) = self.position_of(from)?;
+        let (x2, y2) = self.position_of(to)?;
+        Some((x2.checked_sub(x1)?.max(0), y2.checked_sub(y1)?.max(0)))
+    }
+}
+
+/// where an image part ends up once the archive is unzipped to `unzip_dir`
+pub fn compute_abs_img_path(
+    img_part_name: &str,
+    unzip_dir: &Path,
+) -> Result<PathBuf, IoError> {
+    let img_path = unzip_dir.join(img_part_name);
+    img_path.canonicalize().map_err(|e| IoError::CanonicalizeError {
+        msg: format!("cannot canonicalize img path: {}", img_path.display()),
+        source: e,
+    })
+}
+
+/// resolve a relationship target against the part it is related from,
+/// e.g. `xl/workbook.xml` + `worksheets/sheet1.xml` -> `xl/worksheets/sheet1.xml`.
+/// targets are uris, `media/image%201.png` is the part `xl/media/image 1.png`
+pub fn resolve_part_name(source_part_name: &str, target: &str) -> String {
+    let target = percent_decode(target);
+    let mut segments: Vec<&str> = match target.strip_prefix('/') {
+        Some(_) => Vec::new(),
+        None => source_part_name.split('/').collect(),
+    };
+    // drop the source part's own file name
+    segments.pop();
+    for segment in target.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
             }
+            _ => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
 
-            entries.push(CellImgId { col, row, r_id })
+/// decode the `%XX` escapes of a uri, a string that doesn't decode to utf-8
+/// is kept as it is
+fn percent_decode(uri: &str) -> String {
+    if !uri.contains('%') {
+        return uri.to_owned();
+    }
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| uri.get(i + 1..i + 3))
+            .flatten()
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
         }
     }
+    String::from_utf8(decoded).unwrap_or_else(|_| uri.to_owned())
+}
 
-    entries
-}
-
-pub fn get_rid_img_dict(
-    rels_file: &Path,
-) -> Result<HashMap<String, String>, IoError> {
-    if let Ok(doc) = Document::parse(&(fs::read_to_string(rels_file)?)) {
-        Ok(doc.descendants()
-                .into_iter()
-                .filter_map(|n| {
-                    n.has_tag_name((
-                        "http://schemas.openxmlformats.org/package/2006/relationships",
-                        "Relationship",
-                    ))
-                    .then(|| {
-                        (
-                            n.attribute("Id").unwrap().to_owned(),
-                            n.attribute("Target").unwrap().to_owned(),
-                        )
-                    })
-                })
-                .collect::<HashMap<String, String>>())
-    } else {
-        Ok(HashMap::new())
+/// the .rels part of a part, e.g. `xl/worksheets/sheet1.xml` ->
+/// `xl/worksheets/_rels/sheet1.xml.rels`
+pub fn get_rels_part_name(part_name: &str) -> String {
+    match part_name.rsplit_once('/') {
+        Some((dir, basename)) => format!("{dir}/_rels/{basename}.rels"),
+        None => format!("_rels/{part_name}.rels"),
     }
 }
 
-pub fn generate_col_row_abs_img_dict(
+/// the part a .rels part belongs to, e.g. `xl/workbook.xml` for
+/// `xl/_rels/workbook.xml.rels`, empty for the package's `_rels/.rels`
+pub fn get_rels_source_part_name(rels_part_name: &str) -> String {
+    let (dir, basename) = match rels_part_name.rsplit_once('/') {
+        Some((dir, basename)) => (dir, basename),
+        None => ("", rels_part_name),
+    };
+    let dir = dir.strip_suffix("_rels").unwrap_or(dir);
+    let basename = basename.strip_suffix(".rels").unwrap_or(basename);
+    format!("{dir}{basename}")
+}
+
+fn parse_document<'a>(
+    xml: &'a str,
+    part_name: &str,
+) -> Result<Document<'a>, ParseError> {
+    Document::parse(xml).map_err(|source| ParseError::MalformedXml {
+        part: part_name.to_owned(),
+        source,
+    })
+}
+
+fn get_required_attribute<'a, 'n, 'm, N>(
+    node: &Node<'a, '_>,
+    attr_name: N,
+    part_name: &str,
+) -> Result<&'a str, ParseError>
+where
+    N: Into<ExpandedName<'n, 'm>>,
+{
+    let attr_name = attr_name.into();
+    node.attribute(attr_name).ok_or_else(|| ParseError::MissingAttribute {
+        part: part_name.to_owned(),
+        element: node.tag_name().name().to_owned(),
+        attribute: attr_name.name().to_owned(),
+    })
+}
+
+fn get_required_node_with_tag<'a, 'input>(
+    parent_node: &Node<'a, 'input>,
+    tag_name: &str,
+    part_name: &str,
+) -> Result<Node<'a, 'input>, ParseError> {
+    parent_node.descendants().find(|n| n.has_tag_name(tag_name)).ok_or_else(
+        || ParseError::MissingElement {
+            part: part_name.to_owned(),
+            element: parent_node.tag_name().name().to_owned(),
+            child: tag_name.to_owned(),
+        },
+    )
+}
+
+fn parse_node_text_to_i64(
+    node: &Node,
+    part_name: &str,
+) -> Result<i64, ParseError> {
+    let text = node.text().unwrap_or_default();
+    text.trim().parse::<i64>().map_err(|_| ParseError::InvalidValue {
+        part: part_name.to_owned(),
+        element: node.tag_name().name().to_owned(),
+        value: text.to_owned(),
+    })
+}
+
+/// a 0-based index in a node's text as 1-based, e.g. `<xdr:col>0</xdr:col>`
+/// is column 1
+fn parse_node_text_to_index(
+    node: &Node,
+    part_name: &str,
+) -> Result<i64, ParseError> {
+    parse_node_text_to_i64(node, part_name)?.checked_add(1).ok_or_else(|| {
+        ParseError::InvalidValue {
+            part: part_name.to_owned(),
+            element: node.tag_name().name().to_owned(),
+            value: node.text().unwrap_or_default().to_owned(),
+        }
+    })
+}
+
+fn parse_attribute_to_i64(
+    node: &Node,
+    attr_name: &str,
+    part_name: &str,
+) -> Result<i64, ParseError> {
+    let value = get_required_attribute(node, attr_name, part_name)?;
+    value.parse::<i64>().map_err(|_| ParseError::InvalidValue {
+        part: part_name.to_owned(),
+        element: node.tag_name().name().to_owned(),
+        value: value.to_owned(),
+    })
+}
+
+/// the first descendant named `tag_name` in any of `namespaces`
+fn get_node_with_tag_namespace<'a>(
+    parent_node: &'a Node,
+    namespaces: &[&str],
+    tag_name: &'a str,
+) -> Option<Node<'a, 'a>> {
+    parent_node.descendants().find(|n| {
+        n.has_tag_name(tag_name)
+            && n.tag_name()
+                .namespace()
+                .is_some_and(|ns| namespaces.contains(&ns))
+    })
+}
+
+/// an `r:` attribute, in the transitional or the strict namespace
+fn get_r_attribute<'a>(
+    node: &Node<'a, '_>,
+    attr_name: &str,
+) -> Option<&'a str> {
+    NS_R.iter().find_map(|ns| node.attribute((*ns, attr_name)))
+}
+
+fn get_required_r_attribute<'a>(
+    node: &Node<'a, '_>,
+    attr_name: &str,
+    part_name: &str,
+) -> Result<&'a str, ParseError> {
+    get_r_attribute(node, attr_name).ok_or_else(|| {
+        ParseError::MissingAttribute {
+            part: part_name.to_owned(),
+            element: node.tag_name().name().to_owned(),
+            attribute: attr_name.to_owned(),
+        }
+    })
+}
+
+fn convert_node_text_to_i64(node: &Node) -> Option<i64> {
+    node.text().and_then(|txt| txt.parse::<i64>().ok())
+}
+
+/// a .rels file contains the info: relationship id, its type, target and
+/// target mode, get a map of {relationship id: relationship}
+///
+/// internal targets are resolved against the part the .rels file belongs
+/// to
+pub fn get_relationships(
+    rels_xml: &str,
+    rels_part_name: &str,
+) -> Result<HashMap<String, Relationship>, ParseError> {
+    let doc = parse_document(rels_xml, rels_part_name)?;
+    let source_part_name = get_rels_source_part_name(rels_part_name);
+    doc.descendants()
+        .filter(|n| {
+            n.has_tag_name((
+                "http://schemas.openxmlformats.org/package/2006/relationships",
+                "Relationship",
+            ))
+        })
+        .map(|n| {
+            let rel_type = get_required_attribute(&n, "Type", rels_part_name)?;
+            let target = get_required_attribute(&n, "Target", rels_part_name)?;
+            let external = n.attribute("TargetMode") == Some("External");
+            let rel = Relationship {
+                kind: RelationshipKind::from_type(rel_type),
+                rel_type: rel_type.to_owned(),
+                target: target.to_owned(),
+                part_name: (!external && !target.starts_with('#'))
+                    .then(|| resolve_part_name(&source_part_name, target)),
+                external,
+            };
+            Ok((
+                get_required_attribute(&n, "Id", rels_part_name)?.to_owned(),
+                rel,
+            ))
+        })
+        .collect::<Result<HashMap<String, Relationship>, ParseError>>()
+}
+
+pub fn generate_col_row_img_dict(
     col_row_rid: Vec<CellImgId>,
-    rid_img_dict: HashMap<String, String>,
-    media_dir: &Path,
-) -> HashMap<(i64, i64), Vec<PathBuf>> {
-    // dbg!(&col_row_rid);
-    // dbg!(&rid_img_dict);
-    // dbg!(&media_dir);
-    let mut col_row_abs_img_dict: HashMap<(i64, i64), Vec<PathBuf>> =
+    relationships: &HashMap<String, Relationship>,
+) -> HashMap<(i64, i64), Vec<XlsxImage>> {
+    let mut col_row_img_dict: HashMap<(i64, i64), Vec<XlsxImage>> =
         HashMap::new();
     for entry in col_row_rid {
-        // let img = rid_img_dict.get(&(entry.r_id.unwrap()));
-        // dbg!(&entry.r_id);
-        let img = entry.r_id.and_then(|id| rid_img_dict.get(&id));
-        if let Some(relative_img_path) = img {
-            let relative_img_path = Path::new(relative_img_path);
-            let abs_img_path =
-                compute_abs_img_path(relative_img_path, media_dir);
-            if let Some(imgs) = col_row_abs_img_dict
-                .get_mut(&(entry.col.unwrap(), entry.row.unwrap()))
-            {
-                imgs.push(abs_img_path);
+        let rel = entry.r_id.and_then(|id| relationships.get(&id));
+        if let Some(rel) = rel {
+            let source = if entry.linked {
+                ImageSource::Linked(rel.target.clone())
+            } else if let Some(source) = rel.to_image_source() {
+                source
             } else {
-                col_row_abs_img_dict.insert(
-                    (entry.col.unwrap(), entry.row.unwrap()),
-                    vec![abs_img_path],
-                );
-            }
+                continue;
+            };
+            let img = XlsxImage {
+                source,
+                path: None,
+                anchor: entry.anchor,
+                group_position: entry.group_position,
+                props: entry.props,
+                hyperlink: entry
+                    .hyperlink_r_id
+                    .and_then(|id| relationships.get(&id))
+                    .map(Relationship::to_hyperlink),
+                transform: entry.transform,
+                svg_part_name: entry
+                    .svg_r_id
+                    .and_then(|id| relationships.get(&id))
+                    .and_then(|rel| rel.part_name.clone()),
+                kind: entry.kind,
+            };
+            col_row_img_dict
+                .entry(
+                    entry.cell.unwrap_or((
+                        entry.anchor.from.col,
+                        entry.anchor.from.row,
+                    )),
+                )
+                .or_default()
+                .push(img);
         }
     }
 
-    col_row_abs_img_dict
+    col_row_img_dict
 }
 
-/// xl/workbook.xml contains the info: worksheet id and worksheet name
-pub fn get_worksheet_name_id_map(workbook_xml: &Path) -> HashMap<i64, String> {
-    // let mut worksheet_name_id_map: HashMap<String, String> = HashMap::new();
-    let file_str = std::fs::read_to_string(workbook_xml).unwrap();
-    let doc = Document::parse(&file_str).unwrap();
-    let worksheet_name_id_map = doc
-        .descendants()
-        .into_iter()
-        .filter_map(|n| {
-            if n.has_tag_name("sheet") {
-                let ws_name = n.attribute("name").unwrap();
-                let ws_id = n.attribute("sheetId").unwrap();
-                Some((ws_id.parse::<i64>().unwrap(), ws_name.to_owned()))
-            } else {
-                None
+/// the content types of a package, from `[Content_Types].xml`
+#[derive(Debug, Default)]
+pub struct ContentTypes {
+    /// {lowercased part name without the leading `/`: content type}
+    overrides: HashMap<String, String>,
+    /// {lowercased extension: content type}
+    defaults: HashMap<String, String>,
+}
+
+impl ContentTypes {
+    /// the content type of a part: its `Override`, or else the `Default` of
+    /// its extension, both matched case-insensitively
+    pub fn of_part(&self, part_name: &str) -> Option<&str> {
+        let part_name = part_name.strip_prefix('/').unwrap_or(part_name);
+        let part_name = part_name.to_lowercase();
+        self.overrides
+            .get(&part_name)
+            .or_else(|| {
+                let (_, extension) = part_name.rsplit_once('.')?;
+                self.defaults.get(extension)
+            })
+            .map(String::as_str)
+    }
+}
+
+/// [Content_Types].xml contains the info: the content type of each part by
+/// its name (`Override`) and by its extension (`Default`)
+pub fn get_content_types(
+    content_types_xml: &str,
+    content_types_part_name: &str,
+) -> Result<ContentTypes, ParseError> {
+    let doc = parse_document(content_types_xml, content_types_part_name)?;
+    let mut content_types = ContentTypes::default();
+    for n in doc.descendants() {
+        let (entries, key_attribute) = match n.tag_name().name() {
+            "Override" => (&mut content_types.overrides, "PartName"),
+            "Default" => (&mut content_types.defaults, "Extension"),
+            _ => continue,
+        };
+        let key = get_required_attribute(
+            &n,
+            key_attribute,
+            content_types_part_name,
+        )?;
+        let content_type = get_required_attribute(
+            &n,
+            "ContentType",
+            content_types_part_name,
+        )?;
+        entries.insert(
+            key.strip_prefix('/').unwrap_or(key).to_lowercase(),
+            content_type.to_owned(),
+        );
+    }
+    Ok(content_types)
+}
+
+/// what a worksheet holds about its images, read in a single pass over its
+/// xml
+///
+/// **dimensions**: column widths and row heights, from `sheetFormatPr`,
+/// `cols` and `row`
+///
+/// **header_footer_placeholders**: the sections of `headerFooter` holding a
+/// `&G` picture placeholder
+///
+/// **dispimg_cells**: ((col, row), image id) of the cells holding
+/// `=DISPIMG("ID_xxx",1)` formulas
+///
+/// **vm_cells**: ((col, row), vm) of the cells carrying a value metadata
+/// index
+#[derive(Debug, Default)]
+pub struct Worksheet {
+    pub dimensions: SheetDimensions,
+    pub header_footer_placeholders: HashSet<HeaderFooterSection>,
+    pub ole_objects: OleObjects,
+    pub dispimg_cells: Vec<(ColRow, String)>,
+    pub vm_cells: Vec<(ColRow, i64)>,
+    /// {tag name: r:id} of the worksheet elements pointing at another part,
+    /// e.g. `<legacyDrawing r:id="rId2"/>`
+    element_r_ids: HashMap<String, String>,
+}
+
+impl Worksheet {
+    /// the part name a worksheet element points at through the worksheet's
+    /// relationships, e.g. `xl/drawings/vmlDrawing1.vml` for `legacyDrawing`
+    pub fn element_target(
+        &self,
+        tag_name: &str,
+        sheet_relationships: &SheetRelationships,
+    ) -> Option<String> {
+        self.element_r_ids
+            .get(tag_name)
+            .and_then(|r_id| sheet_relationships.get(r_id))
+            .and_then(|rel| rel.part_name.clone())
+    }
+}
+
+/// parse a worksheet once, collecting its dimensions, the elements pointing
+/// at drawings, its header/footer placeholders, ole objects and the cells
+/// that may hold an image
+pub fn get_worksheet(
+    worksheet_xml: &str,
+    worksheet_part_name: &str,
+    sheet_relationships: &SheetRelationships,
+    policy: AlternateContentPolicy,
+) -> Result<Worksheet, ParseError> {
+    let doc = parse_document(worksheet_xml, worksheet_part_name)?;
+    let root = doc.root_element();
+    let parse_attr = |n: &Node, name: &str| -> Option<f64> {
+        n.attribute(name).and_then(|v| v.parse::<f64>().ok())
+    };
+    let mut worksheet = Worksheet::default();
+    let mut ole_objects_node = None;
+    for n in root.descendants() {
+        let is_top_level = n.parent() == Some(root);
+        if is_top_level {
+            if let Some(r_id) = get_r_attribute(&n, "id") {
+                worksheet
+                    .element_r_ids
+                    .entry(n.tag_name().name().to_owned())
+                    .or_insert_with(|| r_id.to_owned());
             }
-        })
-        .collect::<HashMap<i64, String>>();
-
-    worksheet_name_id_map
-}
-
-pub fn get_sheet_and_drawing_xml_map(
-    worksheet_rels_dir: &Path,
-    drawing_dir: &Path,
-) -> HashMap<String, PathBuf> {
-    let mut sheet_drawing_xml_map = HashMap::new();
-    let mut rels_files = Vec::new();
-    for rel_file in worksheet_rels_dir.read_dir().unwrap() {
-        if let Ok(entry) = rel_file {
-            let entry_path = entry.path();
-            if entry_path.is_file() {
-                let ext = entry_path.extension().unwrap().to_str().unwrap();
-                if ext.to_lowercase() == "rels" {
-                    rels_files.push(entry_path);
+        }
+        match n.tag_name().name() {
+            "sheetFormatPr" => {
+                let dimensions = &mut worksheet.dimensions;
+                dimensions.default_col_width =
+                    parse_attr(&n, "defaultColWidth");
+                dimensions.base_col_width = parse_attr(&n, "baseColWidth");
+                dimensions.default_row_height =
+                    parse_attr(&n, "defaultRowHeight");
+            }
+            "col" => {
+                if let (Some(min), Some(max), Some(width)) = (
+                    parse_attr(&n, "min"),
+                    parse_attr(&n, "max"),
+                    parse_attr(&n, "width"),
+                ) {
+                    let width = if n.attribute("hidden") == Some("1") {
+                        0.0
+                    } else {
+                        width
+                    };
+                    worksheet
+                        .dimensions
+                        .col_widths
+                        .push((min as i64, max as i64, width));
+                }
+            }
+            "row" => {
+                if let (Some(r), Some(ht)) =
+                    (parse_attr(&n, "r"), parse_attr(&n, "ht"))
+                {
+                    let ht = if n.attribute("hidden") == Some("1") {
+                        0.0
+                    } else {
+                        ht
+                    };
+                    worksheet.dimensions.row_heights.insert(r as i64, ht);
+                }
+            }
+            "c" => {
+                let Some(col_row) = n.attribute("r").and_then(parse_cell_ref)
+                else {
+                    continue;
+                };
+                // WPS writes the formula to <f>, and to <v> as well
+                if let Some(img_id) = n
+                    .children()
+                    .filter(|n| n.has_tag_name("f") || n.has_tag_name("v"))
+                    .find_map(|n| n.text().and_then(get_dispimg_id))
+                {
+                    worksheet.dispimg_cells.push((col_row, img_id));
                 }
+                if let Some(vm) =
+                    n.attribute("vm").and_then(|vm| vm.parse::<i64>().ok())
+                {
+                    worksheet.vm_cells.push((col_row, vm));
+                }
+            }
+            "headerFooter" => {
+                worksheet.header_footer_placeholders =
+                    get_header_footer_placeholders(&n);
             }
+            "oleObjects" if is_top_level => ole_objects_node = Some(n),
+            _ => {}
         }
     }
+    // objectPr anchors are placed by the dimensions, complete only once the
+    // whole sheet is read
+    if let Some(ole_objects_node) = ole_objects_node {
+        worksheet.ole_objects = get_ole_objects(
+            &ole_objects_node,
+            worksheet_part_name,
+            sheet_relationships,
+            &worksheet.dimensions,
+            policy,
+        )?;
+    }
+    Ok(worksheet)
+}
 
-    for rels_file in rels_files {
-        let rels_file_clone = rels_file.clone();
-        let rels_file_last_component =
-            rels_file_clone.file_name().unwrap().to_str().unwrap();
-        let file_str = std::fs::read_to_string(&rels_file).unwrap();
-        let doc = Document::parse(&file_str).unwrap();
-        if let Some(relationship_node) = doc
-            .descendants()
-            .into_iter()
-            .find_map(|n| n.has_tag_name("Relationship").then(|| n))
+/// (col, row) of a cell reference, e.g. `B3` -> (2, 3)
+pub fn parse_cell_ref(cell_ref: &str) -> Option<(i64, i64)> {
+    let cell_ref = cell_ref.replace('$', "");
+    let split_at = cell_ref.find(|c: char| c.is_ascii_digit())?;
+    let (letters, digits) = cell_ref.split_at(split_at);
+    // the last column is XFD, more letters can only be malformed
+    if letters.is_empty() || letters.len() > 3 {
+        return None;
+    }
+    let mut col = 0;
+    for c in letters.chars() {
+        if !c.is_ascii_alphabetic() {
+            return None;
+        }
+        col = col * 26 + (c.to_ascii_uppercase() as i64 - 'A' as i64 + 1);
+    }
+    let row = digits.parse::<i64>().ok()?;
+    Some((col, row))
+}
+
+/// xl/cellimages.xml (WPS Office) contains the info: image id and the
+/// relationship id of its picture, get a map of {image id: image}
+pub fn get_cell_img_dict(
+    cell_images_xml: &str,
+    relationships: &HashMap<String, Relationship>,
+    cell_images_part_name: &str,
+) -> Result<HashMap<String, XlsxImage>, ParseError> {
+    let doc = parse_document(cell_images_xml, cell_images_part_name)?;
+
+    let mut cell_img_dict = HashMap::new();
+    for pic_node in doc.descendants().filter(|n| n.has_tag_name("pic")) {
+        // DISPIMG formulas refer to the image by its name, without one it
+        // can't be placed
+        let Some(img_id) = get_c_nv_pr(&pic_node)
+            .and_then(|c_nv_pr_node| c_nv_pr_node.attribute("name"))
+        else {
+            continue;
+        };
+        let Some(part_name) = get_blip_r_id_in_shape(&pic_node)
+            .and_then(|(r_id, _)| relationships.get(&r_id))
+            .and_then(|rel| rel.part_name.clone())
+        else {
+            continue;
+        };
+        // the cell is only known once a DISPIMG formula refers to the image
+        let mut anchor = ImageAnchor::in_cell(0, 0);
+        if let Some(xfrm) = get_node_with_tag(&pic_node, "spPr")
+            .and_then(|sp_pr_node| get_xfrm(&sp_pr_node))
         {
-            if let Some(type_str) = relationship_node.attribute("Type") {
-                if type_str
-                    == "http://schemas.openxmlformats.org/officeDocument/2006/relationships/drawing"
-                {
-                    let drawing_xml_relative = relationship_node.attribute("Target").unwrap();
-                    let drawing_xml_last_filename = Path::new(drawing_xml_relative)
-                        .file_name()
-                        .unwrap()
-                        .to_str()
-                        .unwrap();
-                    let drawing_xml = drawing_dir.join(drawing_xml_last_filename);
-                    sheet_drawing_xml_map.insert(rels_file_last_component.to_owned(), drawing_xml);
-                }
-            }
+            anchor.width = xfrm.ext.0.round() as i64;
+            anchor.height = xfrm.ext.1.round() as i64;
         }
+        let img = XlsxImage {
+            source: ImageSource::Embedded(part_name),
+            path: None,
+            anchor,
+            group_position: None,
+            props: get_image_props(&pic_node),
+            hyperlink: None,
+            transform: get_image_transform(&pic_node),
+            svg_part_name: get_svg_r_id(&pic_node)
+                .and_then(|r_id| relationships.get(&r_id))
+                .and_then(|rel| rel.part_name.clone()),
+            kind: ImageKind::Picture,
+        };
+        cell_img_dict.insert(img_id.to_owned(), img);
     }
+    Ok(cell_img_dict)
+}
+
+/// image id in a `DISPIMG("ID_xxx",1)` formula
+fn get_dispimg_id(formula: &str) -> Option<String> {
+    let args = &formula[formula.find("DISPIMG(")? + "DISPIMG(".len()..];
+    let args = args.trim_start().strip_prefix('"')?;
+    Some(args[..args.find('"')?].to_owned())
+}
+
+/// xl/metadata.xml contains the info: value metadata index (the `vm` of a
+/// cell, 1-based) and the rich value it points to, get a map of
+/// {vm: rich value index}
+pub fn get_vm_rich_value_map(
+    metadata_xml: &str,
+    metadata_part_name: &str,
+) -> Result<HashMap<i64, i64>, ParseError> {
+    let doc = parse_document(metadata_xml, metadata_part_name)?;
+    let Some(metadata_types_node) =
+        doc.descendants().find(|n| n.has_tag_name("metadataTypes"))
+    else {
+        return Ok(HashMap::new());
+    };
+    // rc t="..." is a 1-based index into metadataTypes
+    let rich_value_type_index = metadata_types_node
+        .children()
+        .filter(|n| n.has_tag_name("metadataType"))
+        .position(|n| n.attribute("name") == Some("XLRICHVALUE"))
+        .map(|i| i as i64 + 1);
+    // rc v="..." is a 0-based index into the XLRICHVALUE futureMetadata
+    let rich_value_indices = doc
+        .descendants()
+        .find(|n| {
+            n.has_tag_name("futureMetadata")
+                && n.attribute("name") == Some("XLRICHVALUE")
+        })
+        .map(|future_metadata_node| {
+            future_metadata_node
+                .children()
+                .filter(|n| n.has_tag_name("bk"))
+                .map(|bk| {
+                    get_node_with_tag(&bk, "rvb")
+                        .and_then(|rvb| rvb.attribute("i"))
+                        .and_then(|i| i.parse::<i64>().ok())
+                })
+                .collect::<Vec<Option<i64>>>()
+        })
+        .unwrap_or_default();
+
+    let Some(value_metadata_node) =
+        doc.descendants().find(|n| n.has_tag_name("valueMetadata"))
+    else {
+        return Ok(HashMap::new());
+    };
+    Ok(value_metadata_node
+        .children()
+        .filter(|n| n.has_tag_name("bk"))
+        .enumerate()
+        .filter_map(|(i, bk)| {
+            let rc = get_node_with_tag(&bk, "rc")?;
+            let t = rc.attribute("t")?.parse::<i64>().ok()?;
+            let v = rc.attribute("v")?.parse::<usize>().ok()?;
+            if Some(t) != rich_value_type_index {
+                return None;
+            }
+            let rich_value_index = (*rich_value_indices.get(v)?)?;
+            Some((i as i64 + 1, rich_value_index))
+        })
+        .collect::<HashMap<i64, i64>>())
+}
+
+/// xl/richData/rdrichvalue.xml contains the info: each rich value and its
+/// `_rvRel:LocalImageIdentifier`, an index into richValueRel.xml
+///
+/// the key's position in a rich value comes from rdrichvaluestructure.xml,
+/// defaults to the first value when the structure is unknown
+pub fn get_rich_value_rel_indices(
+    rich_value_xml: &str,
+    rich_value_part_name: &str,
+    rich_value_structure_xml: Option<&str>,
+    rich_value_structure_part_name: &str,
+) -> Result<Vec<Option<i64>>, ParseError> {
+    let doc = parse_document(rich_value_xml, rich_value_part_name)?;
+    let structure_doc = rich_value_structure_xml
+        .map(|xml| parse_document(xml, rich_value_structure_part_name))
+        .transpose()?;
+    let key_positions = structure_doc
+        .as_ref()
+        .map(|structure_doc| {
+            structure_doc
+                .descendants()
+                .filter(|n| n.has_tag_name("s"))
+                .map(|s| {
+                    s.children().filter(|n| n.has_tag_name("k")).position(
+                        |k| {
+                            k.attribute("n")
+                                == Some("_rvRel:LocalImageIdentifier")
+                        },
+                    )
+                })
+                .collect::<Vec<Option<usize>>>()
+        })
+        .unwrap_or_default();
 
-    sheet_drawing_xml_map
+    Ok(doc
+        .descendants()
+        .filter(|n| n.has_tag_name("rv"))
+        .map(|rv| {
+            let position = match rv.attribute("s") {
+                Some(s) if structure_doc.is_some() => {
+                    (*key_positions.get(s.parse::<usize>().ok()?)?)?
+                }
+                _ => 0,
+            };
+            rv.children()
+                .filter(|n| n.has_tag_name("v"))
+                .nth(position)
+                .and_then(|v| convert_node_text_to_i64(&v))
+        })
+        .collect())
 }
 
+/// xl/richData/richValueRel.xml contains the info: relationship ids of the
+/// images, in the order rich values refer to them
+pub fn get_rich_value_rel_rids(
+    rich_value_rel_xml: &str,
+    rich_value_rel_part_name: &str,
+) -> Result<Vec<String>, ParseError> {
+    let doc = parse_document(rich_value_rel_xml, rich_value_rel_part_name)?;
+    doc.descendants()
+        .filter(|n| n.has_tag_name("rel"))
+        .map(|n| {
+            get_required_r_attribute(&n, "id", rich_value_rel_part_name)
+                .map(str::to_owned)
+        })
+        .collect()
+}
 
+/// xl/workbook.xml contains the info: each sheet's id, name and the
+/// relationship id of its worksheet part in xl/_rels/workbook.xml.rels, as
+/// (sheet_id, sheet_name, r_id) in document order
+pub fn get_workbook_sheets(
+    workbook_xml: &str,
+    workbook_part_name: &str,
+) -> Result<Vec<(i64, String, String)>, ParseError> {
+    let doc = parse_document(workbook_xml, workbook_part_name)?;
+    doc.descendants()
+        .filter(|n| n.has_tag_name("sheet"))
+        .map(|n| {
+            let ws_id =
+                parse_attribute_to_i64(&n, "sheetId", workbook_part_name)?;
+            let ws_name =
+                get_required_attribute(&n, "name", workbook_part_name)?;
+            let r_id = get_required_r_attribute(&n, "id", workbook_part_name)?;
+            Ok((ws_id, ws_name.to_owned(), r_id.to_owned()))
+        })
+        .collect()
+}
 
 fn get_node_with_tag<'a>(
     parent_node: &'a Node,
     tag_name: &'a str,
 ) -> Option<Node<'a, 'a>> {
-    parent_node
-        .descendants()
-        .into_iter()
-        .find_map(|n| n.has_tag_name((tag_name)).then(|| n))
+    parent_node.descendants().find(|n| n.has_tag_name(tag_name))
 }
 
+fn get_xfrm(parent_node: &Node) -> Option<Xfrm> {
+    let xfrm_node = get_node_with_tag(parent_node, "xfrm")?;
+    let pair = |tag: &str, a: &str, b: &str| -> Option<(f64, f64)> {
+        let node = xfrm_node.children().find(|n| n.has_tag_name(tag))?;
+        Some((
+            node.attribute(a)?.parse::<f64>().ok()?,
+            node.attribute(b)?.parse::<f64>().ok()?,
+        ))
+    };
+    Some(Xfrm {
+        off: pair("off", "x", "y")?,
+        ext: pair("ext", "cx", "cy")?,
+        ch_off: pair("chOff", "x", "y").unwrap_or_default(),
+        ch_ext: pair("chExt", "cx", "cy").unwrap_or_default(),
+    })
+}
 
-pub fn get_col_row_r_id_sans_xdr(col_row_r_id_file: &Path) -> Vec<CellImgId> {
-    let mut entries: Vec<CellImgId> = Vec::new();
-    let file_str = std::fs::read_to_string(col_row_r_id_file).unwrap();
-    let doc = Document::parse(&file_str).unwrap();
-    let namespaces = doc.root_element().namespaces();
+/// `a:ext` of the `a:xfrm` of the outermost shape or group in an anchor as
+/// (cx, cy), `None` if it has none or it is empty
+fn get_anchor_shape_ext(anchor_node: &Node) -> Option<(i64, i64)> {
+    let sp_pr_node = anchor_node
+        .descendants()
+        .find(|n| n.has_tag_name("spPr") || n.has_tag_name("grpSpPr"))?;
+    let (cx, cy) = get_xfrm(&sp_pr_node)?.ext;
+    (cx > 0.0 && cy > 0.0).then(|| (cx.round() as i64, cy.round() as i64))
+}
 
-    // let ns_default = Some("http://schemas.openxmlformats.org/package/2006/relationships");
-    // let xdr_str = "http://schemas.openxmlformats.org/drawingml/2006/spreadsheetDrawing";
-    // let ns_r = get_namespace_str(namespaces, "r");
-    let ns_xdr = get_namespace_str(&namespaces, "xdr");
-    let mut ns_a = get_namespace_str(&namespaces, "a");
+/// a shape filled with a picture, its `spPr` holds a `blipFill`
+fn has_blip_fill(shape_node: &Node) -> bool {
+    shape_node.children().find(|n| n.has_tag_name("spPr")).is_some_and(
+        |sp_pr| sp_pr.children().any(|n| n.has_tag_name("blipFill")),
+    )
+}
 
-    if !ns_a.is_some() {
-        ns_a = Some("http://schemas.openxmlformats.org/drawingml/2006/main");
-    }
-    let ns_r = Some(
-        "http://schemas.openxmlformats.org/officeDocument/2006/relationships",
+/// where a shape sits in the outermost of `groups`, the groups it's nested
+/// in from the outermost in, `None` if it's not grouped
+fn get_group_position(
+    shape_node: &Node,
+    groups: &[Xfrm],
+) -> Option<GroupPosition> {
+    let outermost = groups.first()?;
+    let shape_xfrm = shape_node
+        .children()
+        .find(|n| n.has_tag_name("spPr"))
+        .and_then(|sp_pr| get_xfrm(&sp_pr))?;
+    let rect = groups.iter().rev().fold(
+        (
+            shape_xfrm.off.0,
+            shape_xfrm.off.1,
+            shape_xfrm.ext.0,
+            shape_xfrm.ext.1,
+        ),
+        |rect, group| group.apply(rect),
     );
+    Some(GroupPosition {
+        x: (rect.0 - outermost.off.0).round() as i64,
+        y: (rect.1 - outermost.off.1).round() as i64,
+        cx: rect.2.round() as i64,
+        cy: rect.3.round() as i64,
+        depth: groups.len(),
+    })
+}
 
-    if ns_a.is_some() && ns_r.is_some() {
-        for two_cell_anchor in doc.descendants().into_iter().filter(|c| {
-            c.has_tag_name("twoCellAnchor")
-            // c.lookup_namespace_uri(Some("xdr")).is_some()
-            //     && c.has_tag_name((ns_xdr.unwrap(), "twoCellAnchor"))
-        }) {
-            let mut col: Option<i64> = None;
-            let mut row: Option<i64> = None;
-            let mut r_id: Option<String> = None;
-
-            if let Some(from_node) = get_node_with_tag(
-                &two_cell_anchor,
-                "from",
-            ) {
-                if let Some(col_node) = get_node_with_tag(
-                    &from_node,
-                    "col",
-                ) {
-                    col = convert_node_text_to_i64(&col_node)
-                        .and_then(|num| Some(num + 1));
+/// every `pic`, and every `sp` filled with a picture, in an anchor,
+/// including the ones nested in group shapes, with their position in the
+/// outermost group
+fn get_image_nodes_in_anchor<'a, 'input>(
+    anchor_node: Node<'a, 'input>,
+) -> Vec<(Node<'a, 'input>, Option<GroupPosition>)> {
+    fn walk<'a, 'input>(
+        node: Node<'a, 'input>,
+        groups: &mut Vec<Xfrm>,
+        pic_nodes: &mut Vec<(Node<'a, 'input>, Option<GroupPosition>)>,
+    ) {
+        for child in node.children().filter(Node::is_element) {
+            match child.tag_name().name() {
+                "pic" => {
+                    pic_nodes
+                        .push((child, get_group_position(&child, groups)));
                 }
-                if let Some(row_node) = get_node_with_tag(
-                    &from_node,
-                    "row",
-                ) {
-                    row = convert_node_text_to_i64(&row_node)
-                        .and_then(|num| Some(num + 1));
+                "sp" if has_blip_fill(&child) => {
+                    pic_nodes
+                        .push((child, get_group_position(&child, groups)));
+                }
+                "grpSp" => {
+                    let xfrm = child
+                        .children()
+                        .find(|n| n.has_tag_name("grpSpPr"))
+                        .and_then(|grp_sppr| get_xfrm(&grp_sppr))
+                        .unwrap_or_default();
+                    groups.push(xfrm);
+                    walk(child, groups, pic_nodes);
+                    groups.pop();
                 }
+                _ => walk(child, groups, pic_nodes),
             }
+        }
+    }
 
-            if let Some(pic_node) = get_node_with_tag(
-                &two_cell_anchor,
-                "pic",
-            ) {
-                if let Some(blip_fill_node) = get_node_with_tag(
-                    &pic_node,
-                    "blipFill",
-                ) {
-                    if let Some(blip_node) = get_node_with_tag_namespace(
-                        &blip_fill_node,
-                        ns_a.unwrap(),
-                        "blip",
-                    ) {
-                        if let Some(embed_ele) = get_node_with_attr_namespace(
-                            &blip_node,
-                            ns_r.unwrap(),
-                            "embed",
-                        ) {
-                            let mut attrs = embed_ele.attributes();
-                            r_id = Some(attrs.next().unwrap().value().to_owned());
-                          
-                        }
-                    }
+    let mut pic_nodes = Vec::new();
+    walk(anchor_node, &mut Vec::new(), &mut pic_nodes);
+    pic_nodes
+}
+
+/// `cNvPr` in the `nvPicPr`, `nvSpPr` or `nvGrpSpPr` of a shape
+fn get_c_nv_pr<'a, 'input>(
+    shape_node: &Node<'a, 'input>,
+) -> Option<Node<'a, 'input>> {
+    shape_node
+        .children()
+        .filter(|n| n.tag_name().name().starts_with("nv"))
+        .flat_map(|nv_pr| nv_pr.children())
+        .find(|n| n.has_tag_name("cNvPr"))
+}
+
+/// relationship id of the `a:hlinkClick` in the `cNvPr` of a shape
+fn get_hyperlink_r_id(shape_node: &Node) -> Option<String> {
+    let hlink_click_node = get_c_nv_pr(shape_node)?
+        .children()
+        .find(|n| n.has_tag_name("hlinkClick"))?;
+    get_r_attribute(&hlink_click_node, "id").map(str::to_owned)
+}
+
+/// crop from the `a:srcRect` of a `pic` or `sp`, rotation and flips from
+/// the `a:xfrm` of its `spPr`
+fn get_image_transform(shape_node: &Node) -> ImageTransform {
+    let attr_i64 = |node: &Node, name: &str| {
+        node.attribute(name).and_then(|v| v.parse::<i64>().ok()).unwrap_or(0)
+    };
+    let attr_bool = |node: &Node, name: &str| {
+        node.attribute(name).is_some_and(|v| v == "1" || v == "true")
+    };
+
+    let mut transform = ImageTransform::default();
+    if let Some(src_rect_node) = get_node_with_tag(shape_node, "blipFill")
+        .and_then(|n| n.children().find(|n| n.has_tag_name("srcRect")))
+    {
+        let crop = Crop {
+            left: attr_i64(&src_rect_node, "l"),
+            top: attr_i64(&src_rect_node, "t"),
+            right: attr_i64(&src_rect_node, "r"),
+            bottom: attr_i64(&src_rect_node, "b"),
+        };
+        transform.crop = (crop != Crop::default()).then_some(crop);
+    }
+    if let Some(xfrm_node) = shape_node
+        .children()
+        .find(|n| n.has_tag_name("spPr"))
+        .and_then(|n| n.children().find(|n| n.has_tag_name("xfrm")))
+    {
+        transform.rotation = attr_i64(&xfrm_node, "rot");
+        transform.flip_h = attr_bool(&xfrm_node, "flipH");
+        transform.flip_v = attr_bool(&xfrm_node, "flipV");
+    }
+    transform
+}
+
+/// `cNvPr` of a `pic` or `sp`, a picture is also hidden when any group
+/// it's nested in is hidden
+fn get_image_props(shape_node: &Node) -> ImageProps {
+    let is_hidden = |node: &Node| {
+        get_c_nv_pr(node)
+            .and_then(|c_nv_pr| c_nv_pr.attribute("hidden"))
+            .is_some_and(|hidden| hidden == "1" || hidden == "true")
+    };
+
+    let c_nv_pr_node = get_c_nv_pr(shape_node);
+    let attr = |name: &str| {
+        c_nv_pr_node.and_then(|n| n.attribute(name)).map(str::to_owned)
+    };
+    ImageProps {
+        name: attr("name"),
+        descr: attr("descr"),
+        title: attr("title"),
+        hidden: is_hidden(shape_node)
+            || shape_node
+                .ancestors()
+                .filter(|n| n.has_tag_name("grpSp"))
+                .any(|group| is_hidden(&group)),
+    }
+}
+
+/// relationship id of a `blip` and whether it's linked, `r:embed` points
+/// at a part in the package, `r:link` at a file outside of it. a blip with
+/// both is linked but keeps a copy in the package, the copy is preferred
+fn get_blip_r_id(blip_node: &Node) -> Option<(String, bool)> {
+    if let Some(r_id) = get_r_attribute(blip_node, "embed") {
+        return Some((r_id.to_owned(), false));
+    }
+    get_r_attribute(blip_node, "link").map(|r_id| (r_id.to_owned(), true))
+}
+
+/// a `pic` is a picture, an `sp` is a shape filled with a picture
+fn get_image_kind(shape_node: &Node) -> ImageKind {
+    if shape_node.tag_name().name() != "sp" {
+        return ImageKind::Picture;
+    }
+    let sp_pr_node = shape_node.children().find(|n| n.has_tag_name("spPr"));
+    let geometry = sp_pr_node
+        .and_then(|n| n.children().find(|n| n.has_tag_name("prstGeom")))
+        .and_then(|n| n.attribute("prst"))
+        .map(str::to_owned);
+    let tiled = sp_pr_node
+        .and_then(|n| n.children().find(|n| n.has_tag_name("blipFill")))
+        .is_some_and(|n| n.children().any(|n| n.has_tag_name("tile")));
+    ImageKind::ShapeFill {
+        geometry,
+        fill_mode: if tiled { FillMode::Tile } else { FillMode::Stretch },
+    }
+}
+
+/// relationship id of the svg original of a picture, kept in the blip's
+/// `a:extLst` as `asvg:svgBlip r:embed`, the blip itself is a png fallback
+fn get_svg_r_id(shape_node: &Node) -> Option<String> {
+    let svg_blip_node = get_node_with_tag(shape_node, "blipFill")?
+        .descendants()
+        .find(|n| n.has_tag_name("svgBlip"))?;
+    get_r_attribute(&svg_blip_node, "embed").map(str::to_owned)
+}
+
+fn get_blip_r_id_in_shape(pic_node: &Node) -> Option<(String, bool)> {
+    let blip_fill_node = get_node_with_tag(pic_node, "blipFill")?;
+    let blip_node =
+        get_node_with_tag_namespace(&blip_fill_node, &NS_A, "blip")?;
+    get_blip_r_id(&blip_node)
+}
+
+/// an `xdr:from` or `xdr:to` marker, col and row are turned 1-based
+fn get_anchor_marker(
+    marker_node: &Node,
+    part_name: &str,
+) -> Result<AnchorMarker, ParseError> {
+    let col_node = get_required_node_with_tag(marker_node, "col", part_name)?;
+    let row_node = get_required_node_with_tag(marker_node, "row", part_name)?;
+    let offset = |tag: &str| match get_node_with_tag(marker_node, tag) {
+        Some(node) => parse_node_text_to_i64(&node, part_name),
+        None => Ok(0),
+    };
+    Ok(AnchorMarker {
+        col: parse_node_text_to_index(&col_node, part_name)?,
+        row: parse_node_text_to_index(&row_node, part_name)?,
+        col_off: offset("colOff")?,
+        row_off: offset("rowOff")?,
+    })
+}
+
+/// `xdr:ext` of a oneCellAnchor or absoluteAnchor as (cx, cy)
+fn get_anchor_ext(
+    anchor_node: &Node,
+    part_name: &str,
+) -> Result<(i64, i64), ParseError> {
+    // a direct child, `a:ext` of the picture's own xfrm must not match
+    let ext_node = anchor_node
+        .children()
+        .find(|n| n.has_tag_name("ext"))
+        .ok_or_else(|| ParseError::MissingElement {
+            part: part_name.to_owned(),
+            element: anchor_node.tag_name().name().to_owned(),
+            child: "ext".to_owned(),
+        })?;
+    Ok((
+        parse_attribute_to_i64(&ext_node, "cx", part_name)?,
+        parse_attribute_to_i64(&ext_node, "cy", part_name)?,
+    ))
+}
+
+/// a vml drawing written by Excel is html-ish and may contain unclosed
+/// `<br>` tags, close them so it parses as xml
+fn close_vml_br_tags(vml_xml: &str) -> String {
+    vml_xml.replace("<br>", "<br/>")
+}
+
+/// every header/footer section holding a `&G` picture placeholder in a
+/// worksheet's `headerFooter`, text before any `&L`/`&C`/`&R` code is
+/// centered
+fn get_header_footer_placeholders(
+    header_footer_node: &Node,
+) -> HashSet<HeaderFooterSection> {
+    let mut sections = HashSet::new();
+    for node in header_footer_node.children().filter(Node::is_element) {
+        let (page, kind) = match node.tag_name().name() {
+            "oddHeader" => (HeaderFooterPage::Odd, HeaderFooterKind::Header),
+            "oddFooter" => (HeaderFooterPage::Odd, HeaderFooterKind::Footer),
+            "evenHeader" => (HeaderFooterPage::Even, HeaderFooterKind::Header),
+            "evenFooter" => (HeaderFooterPage::Even, HeaderFooterKind::Footer),
+            "firstHeader" => {
+                (HeaderFooterPage::First, HeaderFooterKind::Header)
+            }
+            "firstFooter" => {
+                (HeaderFooterPage::First, HeaderFooterKind::Footer)
+            }
+            _ => continue,
+        };
+        let mut position = HeaderFooterPosition::Center;
+        let mut chars = node.text().unwrap_or_default().chars();
+        while let Some(c) = chars.next() {
+            if c != '&' {
+                continue;
+            }
+            match chars.next() {
+                Some('L') => position = HeaderFooterPosition::Left,
+                Some('C') => position = HeaderFooterPosition::Center,
+                Some('R') => position = HeaderFooterPosition::Right,
+                Some('G') => {
+                    sections.insert(HeaderFooterSection {
+                        position,
+                        kind,
+                        page,
+                    });
                 }
-            } else if let Some(pic_node) = get_node_with_tag(&two_cell_anchor, "sp") {
-                if let Some(sppr_node) = get_node_with_tag(&pic_node, "spPr") {
-                    if let Some(blip_fill_node) = get_node_with_tag(&sppr_node, "blipFill") {
-                        if let Some(blip_node) = get_node_with_tag(&blip_fill_node, "blip") {
-                            let mut attrs = blip_node.attributes();
-                            r_id = Some(attrs.next().unwrap().value().to_owned());
+                _ => {}
+            }
+        }
+    }
+    sections
+}
+
+/// the section of a header/footer vml shape by its id: `L`/`C`/`R`, then
+/// `H`/`F`, then `FIRST` or `EVEN` unless it's on odd pages, e.g. `CHFIRST`
+fn parse_header_footer_shape_id(id: &str) -> Option<HeaderFooterSection> {
+    let mut chars = id.chars();
+    let position = match chars.next()? {
+        'L' => HeaderFooterPosition::Left,
+        'C' => HeaderFooterPosition::Center,
+        'R' => HeaderFooterPosition::Right,
+        _ => return None,
+    };
+    let kind = match chars.next()? {
+        'H' => HeaderFooterKind::Header,
+        'F' => HeaderFooterKind::Footer,
+        _ => return None,
+    };
+    let page = match chars.as_str() {
+        "" => HeaderFooterPage::Odd,
+        "FIRST" => HeaderFooterPage::First,
+        "EVEN" => HeaderFooterPage::Even,
+        _ => return None,
+    };
+    Some(HeaderFooterSection { position, kind, page })
+}
+
+/// parse the images of a `legacyDrawingHF` vml drawing, only the ones with
+/// a `&G` placeholder in `placeholders` are shown by Excel and returned
+pub fn get_header_footer_imgs(
+    vml_xml: &str,
+    vml_part_name: &str,
+    relationships: &HashMap<String, Relationship>,
+    placeholders: &HashSet<HeaderFooterSection>,
+) -> Result<Vec<HeaderFooterImage>, ParseError> {
+    let ns_o = "urn:schemas-microsoft-com:office:office";
+    let vml_xml = close_vml_br_tags(vml_xml);
+    let doc = parse_document(&vml_xml, vml_part_name)?;
+
+    let mut imgs = Vec::new();
+    for shape_node in doc.descendants().filter(|n| n.has_tag_name("shape")) {
+        let Some(section) = shape_node
+            .attribute("id")
+            .and_then(parse_header_footer_shape_id)
+            .filter(|section| placeholders.contains(section))
+        else {
+            continue;
+        };
+        let Some(image_data_node) =
+            shape_node.children().find(|n| n.has_tag_name("imagedata"))
+        else {
+            continue;
+        };
+        let Some(rel) = image_data_node
+            .attribute((ns_o, "relid"))
+            .or(get_r_attribute(&image_data_node, "id"))
+            .and_then(|r_id| relationships.get(r_id))
+        else {
+            continue;
+        };
+        let Some(source) = rel.to_image_source() else {
+            continue;
+        };
+
+        // style="position:absolute;width:120pt;height:45pt;z-index:1"
+        let style_pt = |name: &str| {
+            shape_node
+                .attribute("style")?
+                .split(';')
+                .filter_map(|decl| decl.split_once(':'))
+                .find(|(key, _)| key.trim() == name)?
+                .1
+                .trim()
+                .strip_suffix("pt")?
+                .parse::<f64>()
+                .ok()
+                .map(|pt| (pt * EMU_PER_POINT).round() as i64)
+        };
+        imgs.push(HeaderFooterImage {
+            section,
+            source,
+            path: None,
+            title: image_data_node
+                .attribute((ns_o, "title"))
+                .filter(|title| !title.is_empty())
+                .map(str::to_owned),
+            width: style_pt("width").unwrap_or(0),
+            height: style_pt("height").unwrap_or(0),
+        });
+    }
+    Ok(imgs)
+}
+
+/// parse every shape holding an image in a legacy vml drawing: comments
+/// with a picture background (`v:fill o:relid`) and form controls or
+/// pictures (`v:imagedata o:relid`), placed by their `x:Anchor`
+///
+/// a comment belongs to the cell in its `x:Row` / `x:Column`, its anchor is
+/// where the comment box is drawn
+pub fn get_vml_img_ids(
+    vml_xml: &str,
+    vml_part_name: &str,
+    sheet_dimensions: &SheetDimensions,
+    ole_objects: &OleObjects,
+) -> Result<Vec<CellImgId>, ParseError> {
+    let ns_o = "urn:schemas-microsoft-com:office:office";
+    let vml_xml = close_vml_br_tags(vml_xml);
+    let doc = parse_document(&vml_xml, vml_part_name)?;
 
+    let mut entries = Vec::new();
+    for shape_node in doc.descendants().filter(|n| n.has_tag_name("shape")) {
+        let image_data_node =
+            shape_node.descendants().find(|n| n.has_tag_name("imagedata"));
+        let r_id = image_data_node
+            .and_then(|n| {
+                n.attribute((ns_o, "relid")).or(get_r_attribute(&n, "id"))
+            })
+            .or_else(|| {
+                shape_node
+                    .children()
+                    .find(|n| n.has_tag_name("fill"))
+                    .and_then(|n| n.attribute((ns_o, "relid")))
+            });
+        let Some(r_id) = r_id else {
+            continue;
+        };
+        let Some(client_data_node) =
+            shape_node.children().find(|n| n.has_tag_name("ClientData"))
+        else {
+            continue;
+        };
+        let Some(anchor_node) =
+            client_data_node.children().find(|n| n.has_tag_name("Anchor"))
+        else {
+            continue;
+        };
+
+        // LeftColumn, LeftOffset, TopRow, TopOffset, RightColumn,
+        // RightOffset, BottomRow, BottomOffset, 0-based with offsets in px
+        let anchor_text = anchor_node.text().unwrap_or_default();
+        let invalid_anchor = || ParseError::InvalidValue {
+            part: vml_part_name.to_owned(),
+            element: "Anchor".to_owned(),
+            value: anchor_text.to_owned(),
+        };
+        let values = anchor_text
+            .split(',')
+            .map(|v| v.trim().parse::<i64>())
+            .collect::<Result<Vec<i64>, _>>()
+            .ok()
+            .filter(|values| values.len() == 8)
+            .ok_or_else(invalid_anchor)?;
+        let index = |v: i64| v.checked_add(1).ok_or_else(invalid_anchor);
+        let px = |v: i64| (v as f64 * EMU_PER_PIXEL).round() as i64;
+        let from = AnchorMarker {
+            col: index(values[0])?,
+            col_off: px(values[1]),
+            row: index(values[2])?,
+            row_off: px(values[3]),
+        };
+        let to = AnchorMarker {
+            col: index(values[4])?,
+            col_off: px(values[5]),
+            row: index(values[6])?,
+            row_off: px(values[7]),
+        };
+        let (width, height) = sheet_dimensions
+            .size_between(&from, &to)
+            .ok_or_else(invalid_anchor)?;
+        let anchor = ImageAnchor {
+            kind: AnchorKind::TwoCell,
+            from,
+            to: Some(to),
+            pos: None,
+            width,
+            height,
+        };
+
+        let object_type = client_data_node.attribute("ObjectType");
+        // x:Row and x:Column are 0-based
+        let child_index = |tag: &str| {
+            client_data_node
+                .children()
+                .find(|n| n.has_tag_name(tag))
+                .and_then(|n| Some((n, convert_node_text_to_i64(&n)?)))
+                .map(|(n, index)| {
+                    index.checked_add(1).ok_or_else(|| {
+                        ParseError::InvalidValue {
+                            part: vml_part_name.to_owned(),
+                            element: tag.to_owned(),
+                            value: n.text().unwrap_or_default().to_owned(),
                         }
-                    }
-                }
+                    })
+                })
+                .transpose()
+        };
+        // the preview of an ole object is a `Pict` shape with the
+        // object's shapeId, unless objectPr already placed it
+        let ole_kind = shape_node
+            .attribute((ns_o, "spid"))
+            .or(shape_node.attribute("id"))
+            .and_then(get_vml_shape_id)
+            .and_then(|shape_id| ole_objects.vml_shape_kinds.get(shape_id));
+        let (kind, cell) = if let Some(ole_kind) = ole_kind {
+            let Some(ole_kind) = ole_kind else {
+                continue;
+            };
+            (ole_kind.clone(), None)
+        } else if object_type == Some("Note") {
+            let cell = child_index("Column")?.zip(child_index("Row")?);
+            (ImageKind::CommentBackground, cell)
+        } else {
+            (
+                ImageKind::Control {
+                    object_type: object_type.map(str::to_owned),
+                },
+                None,
+            )
+        };
+        // a comment is hidden until hovered, which is not a hidden picture
+        let hidden = kind != ImageKind::CommentBackground
+            && shape_node.attribute("style").is_some_and(|style| {
+                style.replace(' ', "").contains("visibility:hidden")
+            });
+
+        entries.push(CellImgId {
+            r_id: Some(r_id.to_owned()),
+            linked: false,
+            anchor,
+            group_position: None,
+            props: ImageProps {
+                name: shape_node.attribute("id").map(str::to_owned),
+                descr: shape_node.attribute("alt").map(str::to_owned),
+                title: image_data_node
+                    .and_then(|n| n.attribute((ns_o, "title")))
+                    .filter(|title| !title.is_empty())
+                    .map(str::to_owned),
+                hidden,
+            },
+            hyperlink_r_id: None,
+            transform: ImageTransform::default(),
+            svg_r_id: None,
+            kind,
+            cell,
+        });
+    }
+    Ok(entries)
+}
+
+/// the branch of an `mc:AlternateContent` that is read: the first
+/// `mc:Choice` whose `Requires` namespaces are all supported, or the
+/// `mc:Fallback`, in the order `policy` prefers them
+fn get_selected_branch<'a, 'input>(
+    alternate_content_node: &Node<'a, 'input>,
+    policy: AlternateContentPolicy,
+) -> Option<Node<'a, 'input>> {
+    let fallback = alternate_content_node
+        .children()
+        .find(|n| n.has_tag_name((NS_MC, "Fallback")));
+    let choice = alternate_content_node
+        .children()
+        .filter(|n| n.has_tag_name((NS_MC, "Choice")))
+        .find(|choice| {
+            choice
+                .attribute("Requires")
+                .unwrap_or_default()
+                .split_whitespace()
+                .all(|prefix| {
+                    choice
+                        .lookup_namespace_uri(Some(prefix))
+                        .is_some_and(|ns| SUPPORTED_NAMESPACES.contains(&ns))
+                })
+        });
+    match policy {
+        AlternateContentPolicy::PreferChoice => choice.or(fallback),
+        AlternateContentPolicy::PreferFallback => fallback.or(choice),
+    }
+}
+
+/// a node is read unless it's inside a branch of an `mc:AlternateContent`
+/// other than the selected one
+fn is_in_selected_branch(node: &Node, policy: AlternateContentPolicy) -> bool {
+    node.ancestors().filter(|n| n.tag_name().namespace() == Some(NS_MC)).all(
+        |branch_node| match branch_node.parent_element() {
+            Some(alternate_content_node)
+                if alternate_content_node
+                    .has_tag_name((NS_MC, "AlternateContent")) =>
+            {
+                get_selected_branch(&alternate_content_node, policy)
+                    == Some(branch_node)
+            }
+            _ => true,
+        },
+    )
+}
+
+/// the shapeId of a vml shape id, e.g. `1025` for `_x0000_s1025`
+fn get_vml_shape_id(vml_id: &str) -> Option<&str> {
+    vml_id.rsplit_once("_s").map(|(_, shape_id)| shape_id)
+}
+
+/// the ole objects of a worksheet
+#[derive(Debug, Default)]
+pub struct OleObjects {
+    /// objects placed by their `objectPr` anchor, with the preview image
+    img_ids: Vec<CellImgId>,
+    /// shapeId -> kind of the vml shape holding an object's preview, `None`
+    /// when the object is already in `img_ids`
+    vml_shape_kinds: HashMap<String, Option<ImageKind>>,
+}
+
+impl OleObjects {
+    pub fn into_img_ids(self) -> Vec<CellImgId> {
+        self.img_ids
+    }
+}
+
+/// parse `oleObjects` of a worksheet, an `mc:AlternateContent` holds the
+/// same object twice, with `objectPr` in Choice and without it in Fallback.
+/// an object without an `objectPr` anchor has its preview in the legacy vml
+/// drawing only
+fn get_ole_objects(
+    ole_objects_node: &Node,
+    worksheet_part_name: &str,
+    sheet_relationships: &SheetRelationships,
+    sheet_dimensions: &SheetDimensions,
+    policy: AlternateContentPolicy,
+) -> Result<OleObjects, ParseError> {
+    let mut ole_objects = OleObjects::default();
+    for ole_object_node in ole_objects_node.descendants().filter(|n| {
+        n.has_tag_name("oleObject") && is_in_selected_branch(n, policy)
+    }) {
+        let shape_id = ole_object_node.attribute("shapeId");
+        let kind = ImageKind::OleObject {
+            prog_id: ole_object_node.attribute("progId").map(str::to_owned),
+            embedding: get_r_attribute(&ole_object_node, "id")
+                .and_then(|r_id| sheet_relationships.get(r_id))
+                .and_then(|rel| rel.part_name.clone()),
+        };
+        let object_pr_node = ole_object_node
+            .children()
+            .find(|n| n.has_tag_name("objectPr"))
+            .filter(|n| get_r_attribute(n, "id").is_some());
+        // without an objectPr anchor the preview is placed by the object's
+        // shape in the legacy vml drawing
+        let Some((object_pr_node, anchor_node)) =
+            object_pr_node.and_then(|object_pr_node| {
+                let anchor_node = object_pr_node
+                    .children()
+                    .find(|n| n.has_tag_name("anchor"))?;
+                Some((object_pr_node, anchor_node))
+            })
+        else {
+            if let Some(shape_id) = shape_id {
+                ole_objects
+                    .vml_shape_kinds
+                    .insert(shape_id.to_owned(), Some(kind));
+            }
+            continue;
+        };
+        let anchor = get_image_anchor(
+            &anchor_node,
+            AnchorKind::TwoCell,
+            worksheet_part_name,
+            sheet_dimensions,
+        )?;
+        if let Some(shape_id) = shape_id {
+            ole_objects.vml_shape_kinds.insert(shape_id.to_owned(), None);
+        }
+        ole_objects.img_ids.push(CellImgId {
+            r_id: get_r_attribute(&object_pr_node, "id").map(str::to_owned),
+            linked: false,
+            anchor,
+            group_position: None,
+            props: ImageProps {
+                descr: object_pr_node.attribute("altText").map(str::to_owned),
+                ..Default::default()
+            },
+            hyperlink_r_id: None,
+            transform: ImageTransform::default(),
+            svg_r_id: None,
+            kind,
+            cell: None,
+        });
+    }
+    Ok(ole_objects)
+}
+
+/// the full geometry of an anchor, cells and offsets that the anchor does
+/// not store are computed from `sheet_dimensions`
+fn get_image_anchor(
+    anchor_node: &Node,
+    kind: AnchorKind,
+    part_name: &str,
+    sheet_dimensions: &SheetDimensions,
+) -> Result<ImageAnchor, ParseError> {
+    let mut anchor = ImageAnchor::in_cell(1, 1);
+    anchor.kind = kind;
+    let invalid_ext = |(cx, cy)| ParseError::InvalidValue {
+        part: part_name.to_owned(),
+        element: "ext".to_owned(),
+        value: format!("cx={cx} cy={cy}"),
+    };
+    match kind {
+        AnchorKind::TwoCell => {
+            let from_node =
+                get_required_node_with_tag(anchor_node, "from", part_name)?;
+            let to_node =
+                get_required_node_with_tag(anchor_node, "to", part_name)?;
+            let from = get_anchor_marker(&from_node, part_name)?;
+            let to = get_anchor_marker(&to_node, part_name)?;
+            anchor.from = from;
+            anchor.to = Some(to);
+            // the shape's own size, the cells in between only give an
+            // estimate from the column widths and row heights
+            (anchor.width, anchor.height) =
+                match get_anchor_shape_ext(anchor_node) {
+                    Some(ext) => ext,
+                    None => sheet_dimensions
+                        .size_between(&from, &to)
+                        .ok_or_else(|| ParseError::InvalidValue {
+                            part: part_name.to_owned(),
+                            element: anchor_node.tag_name().name().to_owned(),
+                            value: format!("from {from:?} to {to:?}"),
+                        })?,
+                };
+        }
+        // only a twoCellAnchor stores its bottom right cell, for the others
+        // it's the cell covering the far corner of the extent
+        AnchorKind::OneCell => {
+            let from_node =
+                get_required_node_with_tag(anchor_node, "from", part_name)?;
+            let from = get_anchor_marker(&from_node, part_name)?;
+            let ext = get_anchor_ext(anchor_node, part_name)?;
+            let to = sheet_dimensions
+                .position_of(&from)
+                .and_then(|pos| sheet_dimensions.marker_at_offset(pos, ext))
+                .ok_or_else(|| invalid_ext(ext))?;
+            anchor.from = from;
+            anchor.to = Some(to);
+            (anchor.width, anchor.height) = ext;
+        }
+        AnchorKind::Absolute => {
+            let pos_node =
+                get_required_node_with_tag(anchor_node, "pos", part_name)?;
+            let x = parse_attribute_to_i64(&pos_node, "x", part_name)?;
+            let y = parse_attribute_to_i64(&pos_node, "y", part_name)?;
+            let ext = get_anchor_ext(anchor_node, part_name)?;
+            let to = sheet_dimensions
+                .marker_at_offset((x, y), ext)
+                .ok_or_else(|| invalid_ext(ext))?;
+            anchor.from = sheet_dimensions.marker_at(x, y);
+            anchor.to = Some(to);
+            anchor.pos = Some((x, y));
+            (anchor.width, anchor.height) = ext;
+        }
+        AnchorKind::InCell => {}
+    }
+    Ok(anchor)
+}
+
+/// parse every twoCellAnchor, oneCellAnchor and absoluteAnchor in a drawing,
+/// absoluteAnchor has no cell so the covering cell is computed from
+/// `sheet_dimensions`
+pub fn get_col_row_r_id_sans_xdr(
+    drawing_xml: &str,
+    drawing_part_name: &str,
+    sheet_dimensions: &SheetDimensions,
+    policy: AlternateContentPolicy,
+) -> Result<Vec<CellImgId>, ParseError> {
+    let mut entries: Vec<CellImgId> = Vec::new();
+    let doc = parse_document(drawing_xml, drawing_part_name)?;
+
+    for (anchor_node, anchor_kind) in doc
+        .descendants()
+        .filter_map(|c| {
+            let anchor_kind = match c.tag_name().name() {
+                "twoCellAnchor" => AnchorKind::TwoCell,
+                "oneCellAnchor" => AnchorKind::OneCell,
+                "absoluteAnchor" => AnchorKind::Absolute,
+                _ => return None,
+            };
+            Some((c, anchor_kind))
+        })
+        .filter(|(c, _)| is_in_selected_branch(c, policy))
+    {
+        let anchor = get_image_anchor(
+            &anchor_node,
+            anchor_kind,
+            drawing_part_name,
+            sheet_dimensions,
+        )?;
+
+        for (shape_node, group_position) in
+            get_image_nodes_in_anchor(anchor_node).into_iter().filter(
+                |(shape_node, _)| is_in_selected_branch(shape_node, policy),
+            )
+        {
+            let blip_r_id = get_blip_r_id_in_shape(&shape_node);
+            entries.push(CellImgId {
+                linked: blip_r_id.as_ref().is_some_and(|(_, l)| *l),
+                r_id: blip_r_id.map(|(r_id, _)| r_id),
+                anchor,
+                group_position,
+                props: get_image_props(&shape_node),
+                hyperlink_r_id: get_hyperlink_r_id(&shape_node),
+                transform: get_image_transform(&shape_node),
+                svg_r_id: get_svg_r_id(&shape_node),
+                kind: get_image_kind(&shape_node),
+                cell: None,
+            });
+        }
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::tests::rels;
+
+    /// the relationships of a .rels part of (id, type, target)
+    fn relationships(
+        rels_part_name: &str,
+        relationships: &[(&str, &str, &str)],
+    ) -> HashMap<String, Relationship> {
+        get_relationships(&rels(relationships), rels_part_name).unwrap()
+    }
+
+    /// default columns are 64px wide, default rows 15pt high
+    const COL_EMU: i64 = 64 * 9525;
+    const ROW_EMU: i64 = 15 * 12700;
+
+    #[test]
+    fn marker_at_default_dimensions() {
+        let dimensions = SheetDimensions::default();
+        assert_eq!(dimensions.marker_at(0, 0), AnchorMarker::at_cell(1, 1));
+        assert_eq!(
+            dimensions.marker_at(COL_EMU + 100, 2 * ROW_EMU + 5),
+            AnchorMarker { col: 2, row: 3, col_off: 100, row_off: 5 }
+        );
+        // a point on a cell border is in the next cell
+        assert_eq!(
+            dimensions.marker_at(COL_EMU, ROW_EMU),
+            AnchorMarker::at_cell(2, 2)
+        );
+        let marker = AnchorMarker { col: 4, row: 7, col_off: 10, row_off: 20 };
+        let (x, y) = dimensions.position_of(&marker).unwrap();
+        assert_eq!(dimensions.marker_at(x, y), marker);
+    }
+
+    #[test]
+    fn marker_at_custom_dimensions() {
+        let dimensions = SheetDimensions {
+            // 10 characters are 70px
+            col_widths: vec![(1, 2, 10.0)],
+            row_heights: HashMap::from([(1, 0.0), (2, 30.0)]),
+            ..Default::default()
+        };
+        // the hidden first row takes no space
+        assert_eq!(
+            dimensions.marker_at(2 * 70 * 9525, 0),
+            AnchorMarker::at_cell(3, 2)
+        );
+        assert_eq!(
+            dimensions.marker_at(70 * 9525 + 1, 30 * 12700 + 1),
+            AnchorMarker { col: 2, row: 3, col_off: 1, row_off: 1 }
+        );
+    }
+
+    #[test]
+    fn xfrm_apply_group_transform() {
+        // a group drawn twice the size of its child space, moved by 1000
+        let group = Xfrm {
+            off: (1000.0, 1000.0),
+            ext: (2000.0, 2000.0),
+            ch_off: (0.0, 0.0),
+            ch_ext: (1000.0, 1000.0),
+        };
+        assert_eq!(
+            group.apply((500.0, 250.0, 500.0, 100.0)),
+            (2000.0, 1500.0, 1000.0, 200.0)
+        );
+        let moved_child_space = Xfrm { ch_off: (500.0, 500.0), ..group };
+        assert_eq!(
+            moved_child_space.apply((500.0, 500.0, 10.0, 10.0)),
+            (1000.0, 1000.0, 20.0, 20.0)
+        );
+        // an empty child space doesn't scale
+        let unscaled = Xfrm { off: (10.0, 20.0), ..Xfrm::default() };
+        assert_eq!(
+            unscaled.apply((1.0, 2.0, 3.0, 4.0)),
+            (11.0, 22.0, 3.0, 4.0)
+        );
+    }
+
+    #[test]
+    fn cell_ref() {
+        assert_eq!(parse_cell_ref("B3"), Some((2, 3)));
+        assert_eq!(parse_cell_ref("b3"), Some((2, 3)));
+        assert_eq!(parse_cell_ref("$AA$10"), Some((27, 10)));
+        assert_eq!(parse_cell_ref("XFD1048576"), Some((16_384, 1_048_576)));
+        assert_eq!(parse_cell_ref("ABCD1"), None);
+        assert_eq!(parse_cell_ref("12"), None);
+        assert_eq!(parse_cell_ref("A"), None);
+        assert_eq!(parse_cell_ref("A-1"), None);
+        assert_eq!(parse_cell_ref("\u{c4}1"), None);
+    }
+
+    #[test]
+    fn dispimg_id() {
+        assert_eq!(
+            get_dispimg_id(r#"_xlfn.DISPIMG("ID_1A2B",1)"#).as_deref(),
+            Some("ID_1A2B")
+        );
+        assert_eq!(
+            get_dispimg_id(r#"=DISPIMG( "ID_1A2B" ,1)"#).as_deref(),
+            Some("ID_1A2B")
+        );
+        assert_eq!(get_dispimg_id("DISPIMG(A1,1)"), None);
+        assert_eq!(get_dispimg_id(r#"DISPIMG("ID_1A2B"#), None);
+        assert_eq!(get_dispimg_id("SUM(A1:A3)"), None);
+    }
+
+    #[test]
+    fn cell_img_dict() {
+        let cell_images_xml = r#"<etc:cellImages
+            xmlns:etc="http://www.wps.cn/officeDocument/2017/etCustomData"
+            xmlns:xdr="http://schemas.openxmlformats.org/drawingml/2006/spreadsheetDrawing"
+            xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main"
+            xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
+            <etc:cellImage>
+                <xdr:pic>
+                    <xdr:nvPicPr><xdr:cNvPr id="2" name="ID_1A2B" descr="a cat"/><xdr:cNvPicPr/></xdr:nvPicPr>
+                    <xdr:blipFill><a:blip r:embed="rId1"/></xdr:blipFill>
+                    <xdr:spPr><a:xfrm><a:off x="0" y="0"/><a:ext cx="1000" cy="2000"/></a:xfrm></xdr:spPr>
+                </xdr:pic>
+            </etc:cellImage>
+            <etc:cellImage>
+                <xdr:pic>
+                    <xdr:nvPicPr><xdr:cNvPr id="3"/><xdr:cNvPicPr/></xdr:nvPicPr>
+                    <xdr:blipFill><a:blip r:embed="rId2"/></xdr:blipFill>
+                </xdr:pic>
+            </etc:cellImage>
+        </etc:cellImages>"#;
+        let relationships = relationships(
+            "xl/_rels/cellimages.xml.rels",
+            &[
+                ("rId1", "image", "media/image1.png"),
+                ("rId2", "image", "media/image2.png"),
+            ],
+        );
+        let cell_img_dict = get_cell_img_dict(
+            cell_images_xml,
+            &relationships,
+            "xl/cellimages.xml",
+        )
+        .unwrap();
+        // a picture without a name can't be referred to by DISPIMG
+        assert_eq!(cell_img_dict.keys().collect::<Vec<_>>(), ["ID_1A2B"]);
+        let img = &cell_img_dict["ID_1A2B"];
+        assert_eq!(img.part_name(), Some("xl/media/image1.png"));
+        assert_eq!((img.anchor.width, img.anchor.height), (1000, 2000));
+        assert_eq!(img.props.descr.as_deref(), Some("a cat"));
+    }
+
+    #[test]
+    fn worksheet_dispimg_cells() {
+        let worksheet_xml = r#"<worksheet
+            xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main">
+            <sheetData>
+                <row r="2">
+                    <c r="B2" t="str"><f>_xlfn.DISPIMG("ID_1A2B",1)</f><v>=DISPIMG("ID_1A2B",1)</v></c>
+                    <c r="C2" t="str"><v>=DISPIMG("ID_3C4D",1)</v></c>
+                    <c r="D2" t="str"><v>DISPIMG</v></c>
+                    <c t="str"><f>_xlfn.DISPIMG("ID_5E6F",1)</f></c>
+                </row>
+            </sheetData>
+        </worksheet>"#;
+        let worksheet = get_worksheet(
+            worksheet_xml,
+            "xl/worksheets/sheet1.xml",
+            &SheetRelationships::default(),
+            AlternateContentPolicy::default(),
+        )
+        .unwrap();
+        // once per cell, a cell without a reference can't be placed
+        assert_eq!(
+            worksheet.dispimg_cells,
+            [((2, 2), "ID_1A2B".to_owned()), ((3, 2), "ID_3C4D".to_owned())]
+        );
+    }
+
+    #[test]
+    fn vm_rich_value_map() {
+        let metadata_xml = r#"<metadata
+            xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"
+            xmlns:xlrd="http://schemas.microsoft.com/office/spreadsheetml/2017/richdata">
+            <metadataTypes count="2">
+                <metadataType name="XLDAPR" minSupportedVersion="120000"/>
+                <metadataType name="XLRICHVALUE" minSupportedVersion="120000"/>
+            </metadataTypes>
+            <futureMetadata name="XLDAPR" count="1">
+                <bk><extLst><ext uri="{bdbb8cdc-fa1e-496e-a857-3c3f30c029c3}"/></extLst></bk>
+            </futureMetadata>
+            <futureMetadata name="XLRICHVALUE" count="2">
+                <bk><extLst><ext uri="{3e2802c4-a4d2-4d8b-9148-e3be6c30e623}"><xlrd:rvb i="4"/></ext></extLst></bk>
+                <bk><extLst><ext uri="{3e2802c4-a4d2-4d8b-9148-e3be6c30e623}"><xlrd:rvb i="7"/></ext></extLst></bk>
+            </futureMetadata>
+            <valueMetadata count="3">
+                <bk><rc t="2" v="1"/></bk>
+                <bk><rc t="1" v="0"/></bk>
+                <bk><rc t="2" v="0"/></bk>
+            </valueMetadata>
+        </metadata>"#;
+        // vm and t are 1-based, v is 0-based, the second vm is a dynamic
+        // array rather than a rich value
+        assert_eq!(
+            get_vm_rich_value_map(metadata_xml, "xl/metadata.xml").unwrap(),
+            HashMap::from([(1, 7), (3, 4)])
+        );
+    }
+
+    const RICH_VALUE_XML: &str = r#"<rvData
+        xmlns="http://schemas.microsoft.com/office/spreadsheetml/2017/richdata"
+        count="3">
+        <rv s="0"><v>5</v><v>0</v></rv>
+        <rv s="1"><v>1</v><v>5</v><v>alt text</v></rv>
+        <rv s="2"><v>alt text</v></rv>
+    </rvData>"#;
+
+    #[test]
+    fn rich_value_rel_indices_with_structure() {
+        let rich_value_structure_xml = r#"<rvStructures
+            xmlns="http://schemas.microsoft.com/office/spreadsheetml/2017/richdata"
+            count="3">
+            <s t="_localImage">
+                <k n="CalcOrigin" t="i"/>
+                <k n="_rvRel:LocalImageIdentifier" t="i"/>
+            </s>
+            <s t="_localImage">
+                <k n="_rvRel:LocalImageIdentifier" t="i"/>
+                <k n="CalcOrigin" t="i"/>
+                <k n="Text" t="s"/>
+            </s>
+            <s t="_error"><k n="Text" t="s"/></s>
+        </rvStructures>"#;
+        // the key is looked up in each rich value's own structure
+        assert_eq!(
+            get_rich_value_rel_indices(
+                RICH_VALUE_XML,
+                "xl/richData/rdrichvalue.xml",
+                Some(rich_value_structure_xml),
+                "xl/richData/rdrichvaluestructure.xml",
+            )
+            .unwrap(),
+            [Some(0), Some(1), None]
+        );
+    }
+
+    #[test]
+    fn rich_value_rel_indices_without_structure() {
+        // the first value is taken
+        assert_eq!(
+            get_rich_value_rel_indices(
+                RICH_VALUE_XML,
+                "xl/richData/rdrichvalue.xml",
+                None,
+                "xl/richData/rdrichvaluestructure.xml",
+            )
+            .unwrap(),
+            [Some(5), Some(1), None]
+        );
+    }
+
+    #[test]
+    fn rich_value_rel_rids() {
+        let part_name = "xl/richData/richValueRel.xml";
+        let rich_value_rel_xml = r#"<richValueRels
+            xmlns="http://schemas.microsoft.com/office/spreadsheetml/2022/richvaluerel"
+            xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
+            <rel r:id="rId2"/>
+            <rel r:id="rId1"/>
+        </richValueRels>"#;
+        assert_eq!(
+            get_rich_value_rel_rids(rich_value_rel_xml, part_name).unwrap(),
+            ["rId2", "rId1"]
+        );
+        let missing_r_id = r#"<richValueRels
+            xmlns="http://schemas.microsoft.com/office/spreadsheetml/2022/richvaluerel">
+            <rel/>
+        </richValueRels>"#;
+        assert!(matches!(
+            get_rich_value_rel_rids(missing_r_id, part_name),
+            Err(ParseError::MissingAttribute { .. })
+        ));
+    }
+
+    #[test]
+    fn anchor_index_overflow_is_invalid() {
+        let drawing_xml = r#"<xdr:wsDr
+            xmlns:xdr="http://schemas.openxmlformats.org/drawingml/2006/spreadsheetDrawing"
+            xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main"
+            xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
+            <xdr:oneCellAnchor>
+                <xdr:from><xdr:col>9223372036854775807</xdr:col><xdr:colOff>0</xdr:colOff><xdr:row>0</xdr:row><xdr:rowOff>0</xdr:rowOff></xdr:from>
+                <xdr:ext cx="100" cy="100"/>
+                <xdr:pic>
+                    <xdr:nvPicPr><xdr:cNvPr id="2" name="Picture 1"/><xdr:cNvPicPr/></xdr:nvPicPr>
+                    <xdr:blipFill><a:blip r:embed="rId1"/></xdr:blipFill>
+                </xdr:pic>
+                <xdr:clientData/>
+            </xdr:oneCellAnchor>
+        </xdr:wsDr>"#;
+        assert!(matches!(
+            get_col_row_r_id_sans_xdr(
+                drawing_xml,
+                "xl/drawings/drawing1.xml",
+                &SheetDimensions::default(),
+                AlternateContentPolicy::default(),
+            ),
+            Err(ParseError::InvalidValue { element, .. }) if element == "col"
+        ));
+    }
+
+    #[test]
+    fn one_cell_and_absolute_anchors_span_cells() {
+        let pic = |name: &str| {
+            format!(
+                r#"<xdr:pic>
+                    <xdr:nvPicPr><xdr:cNvPr id="2" name="{name}"/><xdr:cNvPicPr/></xdr:nvPicPr>
+                    <xdr:blipFill><a:blip r:embed="rId1"/></xdr:blipFill>
+                </xdr:pic>
+                <xdr:clientData/>"#
+            )
+        };
+        let drawing_xml = format!(
+            r#"<xdr:wsDr
+            xmlns:xdr="http://schemas.openxmlformats.org/drawingml/2006/spreadsheetDrawing"
+            xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main"
+            xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
+            <xdr:oneCellAnchor>
+                <xdr:from><xdr:col>1</xdr:col><xdr:colOff>0</xdr:colOff><xdr:row>1</xdr:row><xdr:rowOff>0</xdr:rowOff></xdr:from>
+                <xdr:ext cx="3000000" cy="500000"/>
+                {}
+            </xdr:oneCellAnchor>
+            <xdr:absoluteAnchor>
+                <xdr:pos x="{COL_EMU}" y="{ROW_EMU}"/>
+                <xdr:ext cx="3000000" cy="500000"/>
+                {}
+            </xdr:absoluteAnchor>
+        </xdr:wsDr>"#,
+            pic("one cell"),
+            pic("absolute")
+        );
+        let img_ids = get_col_row_r_id_sans_xdr(
+            &drawing_xml,
+            "xl/drawings/drawing1.xml",
+            &SheetDimensions::default(),
+            AlternateContentPolicy::default(),
+        )
+        .unwrap();
+        assert_eq!(img_ids.len(), 2);
+        for img_id in img_ids {
+            let anchor = &img_id.anchor;
+            assert_eq!(anchor.from, AnchorMarker::at_cell(2, 2));
+            // 3000000 EMU is 4 default columns and 561600 EMU, 500000 EMU
+            // is 2 default rows and 119000 EMU
+            assert_eq!(
+                anchor.to,
+                Some(AnchorMarker {
+                    col: 6,
+                    row: 4,
+                    col_off: 3_000_000 - 4 * COL_EMU,
+                    row_off: 500_000 - 2 * ROW_EMU,
+                })
+            );
+            assert_eq!(anchor.cell_span(), (5, 3));
+        }
+    }
+
+    /// a legacy vml drawing of `shapes`
+    fn vml(shapes: &str) -> String {
+        format!(
+            r#"<xml xmlns:v="urn:schemas-microsoft-com:vml"
+            xmlns:o="urn:schemas-microsoft-com:office:office"
+            xmlns:x="urn:schemas-microsoft-com:office:excel">{shapes}</xml>"#
+        )
+    }
+
+    #[test]
+    fn vml_img_ids() {
+        // html-ish, with an unclosed <br>
+        let vml_xml = vml(r##"
+            <v:shape id="_x0000_s1025" type="#_x0000_t202"
+                style="position:absolute;visibility:hidden">
+                <v:fill o:relid="rId1" type="frame"/>
+                <v:textbox><div>first line<br>second line</div></v:textbox>
+                <x:ClientData ObjectType="Note">
+                    <x:Anchor>3, 15, 1, 10, 5, 15, 5, 4</x:Anchor>
+                    <x:Row>1</x:Row>
+                    <x:Column>1</x:Column>
+                </x:ClientData>
+            </v:shape>
+            <v:shape id="_x0000_s1026" style="position:absolute; visibility: hidden">
+                <v:imagedata o:relid="rId2" o:title="logo"/>
+                <x:ClientData ObjectType="Pict">
+                    <x:Anchor>0, 0, 0, 0, 1, 0, 1, 0</x:Anchor>
+                </x:ClientData>
+            </v:shape>
+            <v:shape id="_x0000_s1027">
+                <x:ClientData ObjectType="Note">
+                    <x:Anchor>0, 0, 0, 0, 1, 0, 1, 0</x:Anchor>
+                </x:ClientData>
+            </v:shape>"##);
+        let img_ids = get_vml_img_ids(
+            &vml_xml,
+            "xl/drawings/vmlDrawing1.vml",
+            &SheetDimensions::default(),
+            &OleObjects::default(),
+        )
+        .unwrap();
+        // the comment without a picture background has no image
+        assert_eq!(img_ids.len(), 2);
+
+        let comment = &img_ids[0];
+        assert_eq!(comment.r_id.as_deref(), Some("rId1"));
+        assert_eq!(comment.kind, ImageKind::CommentBackground);
+        // the comment belongs to B2, its box is drawn from D2
+        assert_eq!(comment.cell, Some((2, 2)));
+        assert_eq!(
+            comment.anchor.from,
+            AnchorMarker {
+                col: 4,
+                row: 2,
+                col_off: 15 * 9525,
+                row_off: 10 * 9525
             }
+        );
+        // a comment is hidden until hovered
+        assert!(!comment.props.hidden);
+
+        let picture = &img_ids[1];
+        assert_eq!(picture.r_id.as_deref(), Some("rId2"));
+        assert_eq!(
+            picture.kind,
+            ImageKind::Control { object_type: Some("Pict".to_owned()) }
+        );
+        assert_eq!(picture.cell, None);
+        assert_eq!(picture.anchor.from, AnchorMarker::at_cell(1, 1));
+        assert_eq!(picture.anchor.to, Some(AnchorMarker::at_cell(2, 2)));
+        assert_eq!(
+            (picture.anchor.width, picture.anchor.height),
+            (COL_EMU, ROW_EMU)
+        );
+        assert_eq!(picture.props.title.as_deref(), Some("logo"));
+        assert!(picture.props.hidden);
+    }
+
+    #[test]
+    fn vml_index_overflow_is_invalid() {
+        let shape = |client_data: &str| {
+            vml(&format!(
+                r#"<v:shape id="_x0000_s1025">
+                    <v:fill o:relid="rId1" type="frame"/>
+                    <x:ClientData ObjectType="Note">{client_data}</x:ClientData>
+                </v:shape>"#
+            ))
+        };
+        let invalid_element = |vml_xml: &str| match get_vml_img_ids(
+            vml_xml,
+            "xl/drawings/vmlDrawing1.vml",
+            &SheetDimensions::default(),
+            &OleObjects::default(),
+        ) {
+            Err(ParseError::InvalidValue { element, .. }) => Some(element),
+            _ => None,
+        };
+        assert_eq!(
+            invalid_element(&shape(
+                "<x:Anchor>9223372036854775807, 0, 0, 0, 1, 0, 1, 0</x:Anchor>"
+            ))
+            .as_deref(),
+            Some("Anchor")
+        );
+        assert_eq!(
+            invalid_element(&shape("<x:Anchor>0, 0</x:Anchor>")).as_deref(),
+            Some("Anchor")
+        );
+        assert_eq!(
+            invalid_element(&shape(
+                "<x:Anchor>0, 0, 0, 0, 1, 0, 1, 0</x:Anchor>\
+                 <x:Row>9223372036854775807</x:Row><x:Column>0</x:Column>"
+            ))
+            .as_deref(),
+            Some("Row")
+        );
+    }
+
+    #[test]
+    fn header_footer_shape_id() {
+        let section = |position, kind, page| {
+            Some(HeaderFooterSection { position, kind, page })
+        };
+        assert_eq!(
+            parse_header_footer_shape_id("LH"),
+            section(
+                HeaderFooterPosition::Left,
+                HeaderFooterKind::Header,
+                HeaderFooterPage::Odd
+            )
+        );
+        assert_eq!(
+            parse_header_footer_shape_id("CFFIRST"),
+            section(
+                HeaderFooterPosition::Center,
+                HeaderFooterKind::Footer,
+                HeaderFooterPage::First
+            )
+        );
+        assert_eq!(
+            parse_header_footer_shape_id("RHEVEN"),
+            section(
+                HeaderFooterPosition::Right,
+                HeaderFooterKind::Header,
+                HeaderFooterPage::Even
+            )
+        );
+        for id in ["", "L", "XH", "LX", "LHODD", "_x0000_s1025"] {
+            assert_eq!(parse_header_footer_shape_id(id), None, "{id}");
+        }
+    }
+
+    #[test]
+    fn header_footer_placeholders() {
+        let doc = Document::parse(
+            r#"<headerFooter differentFirst="1"
+            xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main">
+            <oddHeader>&amp;L&amp;G&amp;CR&amp;&amp;G&amp;R&amp;G</oddHeader>
+            <oddFooter>&amp;G page &amp;P</oddFooter>
+            <firstHeader>&amp;R&amp;"Arial,Bold"&amp;G</firstHeader>
+        </headerFooter>"#,
+        )
+        .unwrap();
+        let section = |position, kind, page| HeaderFooterSection {
+            position,
+            kind,
+            page,
+        };
+        // `&&` is a literal ampersand, the center header is the text R&G
+        assert_eq!(
+            get_header_footer_placeholders(&doc.root_element()),
+            HashSet::from([
+                section(
+                    HeaderFooterPosition::Left,
+                    HeaderFooterKind::Header,
+                    HeaderFooterPage::Odd
+                ),
+                section(
+                    HeaderFooterPosition::Right,
+                    HeaderFooterKind::Header,
+                    HeaderFooterPage::Odd
+                ),
+                section(
+                    HeaderFooterPosition::Center,
+                    HeaderFooterKind::Footer,
+                    HeaderFooterPage::Odd
+                ),
+                section(
+                    HeaderFooterPosition::Right,
+                    HeaderFooterKind::Header,
+                    HeaderFooterPage::First
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn header_footer_imgs() {
+        // html-ish, with an unclosed <br>
+        let vml_xml = vml(r#"
+            <o:shapelayout v:ext="edit"><o:idmap v:ext="edit" data="1"/><br></o:shapelayout>
+            <v:shape id="LH" o:spid="_x0000_s1025"
+                style="position:absolute;margin-left:0;width:120pt;height:45pt;z-index:1">
+                <v:imagedata o:relid="rId1" o:title="logo"/>
+            </v:shape>
+            <v:shape id="CH" style="position:absolute;width:10pt;height:10pt">
+                <v:imagedata o:relid="rId1" o:title="unused"/>
+            </v:shape>"#);
+        let relationships = relationships(
+            "xl/drawings/_rels/vmlDrawingHF1.vml.rels",
+            &[("rId1", "image", "../media/image1.png")],
+        );
+        let left_header = HeaderFooterSection {
+            position: HeaderFooterPosition::Left,
+            kind: HeaderFooterKind::Header,
+            page: HeaderFooterPage::Odd,
+        };
+        // the center header has no &G, its image isn't printed
+        let imgs = get_header_footer_imgs(
+            &vml_xml,
+            "xl/drawings/vmlDrawingHF1.vml",
+            &relationships,
+            &HashSet::from([left_header]),
+        )
+        .unwrap();
+        assert_eq!(
+            imgs,
+            [HeaderFooterImage {
+                section: left_header,
+                source: ImageSource::Embedded(
+                    "xl/media/image1.png".to_owned()
+                ),
+                path: None,
+                title: Some("logo".to_owned()),
+                width: 120 * 12700,
+                height: 45 * 12700,
+            }]
+        );
+    }
 
-            entries.push(CellImgId { col, row, r_id })
+    #[test]
+    fn ole_object_reported_once_per_policy() {
+        // Excel writes the object twice, anchored by objectPr in Choice
+        let worksheet_xml = r#"<worksheet
+            xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"
+            xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"
+            xmlns:mc="http://schemas.openxmlformats.org/markup-compatibility/2006"
+            xmlns:xdr="http://schemas.openxmlformats.org/drawingml/2006/spreadsheetDrawing"
+            xmlns:x14="http://schemas.microsoft.com/office/spreadsheetml/2009/9/main">
+            <sheetData/>
+            <legacyDrawing r:id="rId3"/>
+            <oleObjects>
+                <mc:AlternateContent>
+                    <mc:Choice Requires="x14">
+                        <oleObject progId="Word.Document.12" shapeId="1025" r:id="rId1">
+                            <objectPr defaultSize="0" r:id="rId2" altText="report">
+                                <anchor moveWithCells="1">
+                                    <from><xdr:col>1</xdr:col><xdr:colOff>0</xdr:colOff><xdr:row>2</xdr:row><xdr:rowOff>0</xdr:rowOff></from>
+                                    <to><xdr:col>3</xdr:col><xdr:colOff>0</xdr:colOff><xdr:row>5</xdr:row><xdr:rowOff>0</xdr:rowOff></to>
+                                </anchor>
+                            </objectPr>
+                        </oleObject>
+                    </mc:Choice>
+                    <mc:Fallback>
+                        <oleObject progId="Word.Document.12" shapeId="1025" r:id="rId1"/>
+                    </mc:Fallback>
+                </mc:AlternateContent>
+            </oleObjects>
+        </worksheet>"#;
+        let vml_xml = vml(r##"
+            <v:shape id="_x0000_s1025" type="#_x0000_t75">
+                <v:imagedata o:relid="rId1" o:title=""/>
+                <x:ClientData ObjectType="Pict">
+                    <x:Anchor>1, 0, 2, 0, 3, 0, 5, 0</x:Anchor>
+                    <x:CF>Pict</x:CF>
+                </x:ClientData>
+            </v:shape>"##);
+        let sheet_relationships = SheetRelationships {
+            worksheet: "xl/worksheets/sheet1.xml".to_owned(),
+            relationships: relationships(
+                "xl/worksheets/_rels/sheet1.xml.rels",
+                &[
+                    ("rId1", "oleObject", "../embeddings/oleObject1.bin"),
+                    ("rId2", "image", "../media/image1.emf"),
+                    ("rId3", "vmlDrawing", "../drawings/vmlDrawing1.vml"),
+                ],
+            ),
+        };
+        let kind = ImageKind::OleObject {
+            prog_id: Some("Word.Document.12".to_owned()),
+            embedding: Some("xl/embeddings/oleObject1.bin".to_owned()),
+        };
+        // the preview is the objectPr's image with Choice, the vml shape's
+        // with Fallback
+        for (policy, preview_r_id) in [
+            (AlternateContentPolicy::PreferChoice, "rId2"),
+            (AlternateContentPolicy::PreferFallback, "rId1"),
+        ] {
+            let mut worksheet = get_worksheet(
+                worksheet_xml,
+                "xl/worksheets/sheet1.xml",
+                &sheet_relationships,
+                policy,
+            )
+            .unwrap();
+            let mut img_ids = get_vml_img_ids(
+                &vml_xml,
+                "xl/drawings/vmlDrawing1.vml",
+                &worksheet.dimensions,
+                &worksheet.ole_objects,
+            )
+            .unwrap();
+            img_ids.extend(
+                std::mem::take(&mut worksheet.ole_objects).into_img_ids(),
+            );
+            assert_eq!(img_ids.len(), 1, "{policy:?}");
+            assert_eq!(
+                img_ids[0].r_id.as_deref(),
+                Some(preview_r_id),
+                "{policy:?}"
+            );
+            assert_eq!(img_ids[0].kind, kind, "{policy:?}");
+            assert_eq!(
+                img_ids[0].anchor.from,
+                AnchorMarker::at_cell(2, 3),
+                "{policy:?}"
+            );
         }
     }
 
-    entries
-}
\ No newline at end of file
+    const ALTERNATE_CONTENT_XML: &str = r#"<root
+        xmlns:mc="http://schemas.openxmlformats.org/markup-compatibility/2006"
+        xmlns:a14="http://schemas.microsoft.com/office/drawing/2010/main"
+        xmlns:cx1="http://schemas.microsoft.com/office/drawing/2015/9/8/chartex">
+        <mc:AlternateContent>
+            <mc:Choice Requires="a14"><pic id="choice"/></mc:Choice>
+            <mc:Fallback><pic id="fallback"/></mc:Fallback>
+        </mc:AlternateContent>
+        <mc:AlternateContent>
+            <mc:Choice Requires="cx1"><pic id="unsupported"/></mc:Choice>
+            <mc:Fallback><pic id="unsupported fallback"/></mc:Fallback>
+        </mc:AlternateContent>
+        <pic id="plain"/>
+    </root>"#;
+
+    fn selected_pic_ids(policy: AlternateContentPolicy) -> Vec<String> {
+        let doc = Document::parse(ALTERNATE_CONTENT_XML).unwrap();
+        doc.descendants()
+            .filter(|n| {
+                n.has_tag_name("pic") && is_in_selected_branch(n, policy)
+            })
+            .filter_map(|n| n.attribute("id").map(str::to_owned))
+            .collect()
+    }
+
+    #[test]
+    fn selected_branch() {
+        let doc = Document::parse(ALTERNATE_CONTENT_XML).unwrap();
+        let alternate_content_node = doc
+            .descendants()
+            .find(|n| n.has_tag_name((NS_MC, "AlternateContent")))
+            .unwrap();
+        let branch_name = |policy| {
+            get_selected_branch(&alternate_content_node, policy)
+                .map(|n| n.tag_name().name())
+        };
+        assert_eq!(
+            branch_name(AlternateContentPolicy::PreferChoice),
+            Some("Choice")
+        );
+        assert_eq!(
+            branch_name(AlternateContentPolicy::PreferFallback),
+            Some("Fallback")
+        );
+    }
+
+    #[test]
+    fn in_selected_branch() {
+        assert_eq!(
+            selected_pic_ids(AlternateContentPolicy::PreferChoice),
+            ["choice", "unsupported fallback", "plain"]
+        );
+        assert_eq!(
+            selected_pic_ids(AlternateContentPolicy::PreferFallback),
+            ["fallback", "unsupported fallback", "plain"]
+        );
+    }
+
+    #[test]
+    fn resolve_part_name_relative_and_absolute() {
+        assert_eq!(
+            resolve_part_name("xl/workbook.xml", "worksheets/sheet1.xml"),
+            "xl/worksheets/sheet1.xml"
+        );
+        assert_eq!(
+            resolve_part_name(
+                "xl/drawings/drawing1.xml",
+                "../media/image1.png"
+            ),
+            "xl/media/image1.png"
+        );
+        assert_eq!(
+            resolve_part_name("xl/drawings/drawing1.xml", "./../../a/./b.png"),
+            "a/b.png"
+        );
+        assert_eq!(
+            resolve_part_name("xl/drawings/drawing1.xml", "/xl/images/a.png"),
+            "xl/images/a.png"
+        );
+        assert_eq!(
+            resolve_part_name("", "xl/workbook.xml"),
+            "xl/workbook.xml"
+        );
+    }
+
+    #[test]
+    fn resolve_part_name_keeps_case() {
+        // matched case-insensitively to a zip entry later, by the archive
+        assert_eq!(
+            resolve_part_name(
+                "xl/drawings/drawing1.xml",
+                "../Media/IMAGE1.PNG"
+            ),
+            "xl/Media/IMAGE1.PNG"
+        );
+    }
+
+    #[test]
+    fn resolve_part_name_percent_decodes() {
+        assert_eq!(
+            resolve_part_name(
+                "xl/drawings/drawing1.xml",
+                "../media/image%201.png"
+            ),
+            "xl/media/image 1.png"
+        );
+        assert_eq!(
+            resolve_part_name("xl/workbook.xml", "media/%E2%82%AC.png"),
+            "xl/media/\u{20ac}.png"
+        );
+        // not an escape, or not utf-8 once decoded
+        assert_eq!(
+            resolve_part_name("xl/workbook.xml", "media/100%.png"),
+            "xl/media/100%.png"
+        );
+        assert_eq!(
+            resolve_part_name("xl/workbook.xml", "media/%+1.png"),
+            "xl/media/%+1.png"
+        );
+        assert_eq!(
+            resolve_part_name("xl/workbook.xml", "media/%FF.png"),
+            "xl/media/%FF.png"
+        );
+    }
+
+    #[test]
+    fn rels_source_part_name() {
+        assert_eq!(
+            get_rels_source_part_name("xl/_rels/workbook.xml.rels"),
+            "xl/workbook.xml"
+        );
+        assert_eq!(
+            get_rels_source_part_name("xl/worksheets/_rels/sheet1.xml.rels"),
+            "xl/worksheets/sheet1.xml"
+        );
+        assert_eq!(get_rels_source_part_name("_rels/.rels"), "");
+        for part_name in ["xl/workbook.xml", "xl/drawings/drawing1.xml"] {
+            assert_eq!(
+                get_rels_source_part_name(&get_rels_part_name(part_name)),
+                part_name
+            );
+        }
+    }
+}
@@ -1,47 +1,621 @@
-use super::errors::{IoError, XlsxPathParseError};
-use super::unzip_utils::UnzippedPaths;
+use super::archive::XlsxArchive;
+use super::errors::{
+    Error, IoError, ParseError, XlsxFormatError, XlsxPathParseError,
+};
 use super::*;
 
 use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::fmt;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek};
 use std::path::{Path, PathBuf};
 use std::primitive::str;
 use std::str::FromStr;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// the kind of drawing anchor an image came from
+///
+/// **TwoCell**: moves and sizes with cells, `xdr:twoCellAnchor`
+///
+/// **OneCell**: moves but doesn't size with cells, `xdr:oneCellAnchor`
+///
+/// **Absolute**: doesn't move or size with cells, `xdr:absoluteAnchor`,
+/// its cell is the one covering its top left corner
+///
+/// **InCell**: placed in the cell itself rather than floating over it,
+/// e.g. WPS Office cell images shown with `=DISPIMG("ID_xxx",1)`, or
+/// Excel 365 "Place in Cell" pictures stored as rich values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnchorKind {
+    TwoCell,
+    OneCell,
+    Absolute,
+    InCell,
+}
+
+/// a cell marker of an anchor, `xdr:from` or `xdr:to`
+///
+/// **col**, **row**: the cell, both 1-based like the keys of the image maps
+///
+/// **col_off**, **row_off**: offset into the cell in EMU
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct AnchorMarker {
+    pub col: i64,
+    pub row: i64,
+    pub col_off: i64,
+    pub row_off: i64,
+}
+
+impl AnchorMarker {
+    /// the top left corner of a cell
+    pub fn at_cell(col: i64, row: i64) -> Self {
+        AnchorMarker { col, row, col_off: 0, row_off: 0 }
+    }
+
+    pub fn col_off_px(&self) -> f64 {
+        self.col_off as f64 / parse_xml::EMU_PER_PIXEL
+    }
+
+    pub fn row_off_px(&self) -> f64 {
+        self.row_off as f64 / parse_xml::EMU_PER_PIXEL
+    }
+}
+
+/// where an image sits on the sheet, 1 EMU = 1/9525 px at 96 dpi
+///
+/// **kind**: the kind of anchor the image came from
+///
+/// **from**: the cell covering the image's top left corner
+///
+/// **to**: the cell covering the image's bottom right corner, as stored by
+/// a twoCellAnchor or computed from the extent and the column widths and
+/// row heights for the other anchors. `None` for in-cell pictures
+///
+/// **pos**: (x, y) position on the sheet in EMU,
+/// only set for `AnchorKind::Absolute`
+///
+/// **width**, **height**: size in EMU, 0 when unknown, e.g. for in-cell
+/// pictures that are fitted to their cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ImageAnchor {
+    pub kind: AnchorKind,
+    pub from: AnchorMarker,
+    pub to: Option<AnchorMarker>,
+    pub pos: Option<(i64, i64)>,
+    pub width: i64,
+    pub height: i64,
+}
+
+impl ImageAnchor {
+    /// an image placed in the cell itself
+    pub fn in_cell(col: i64, row: i64) -> Self {
+        ImageAnchor {
+            kind: AnchorKind::InCell,
+            from: AnchorMarker::at_cell(col, row),
+            to: None,
+            pos: None,
+            width: 0,
+            height: 0,
+        }
+    }
+
+    pub fn width_px(&self) -> f64 {
+        self.width as f64 / parse_xml::EMU_PER_PIXEL
+    }
+
+    pub fn height_px(&self) -> f64 {
+        self.height as f64 / parse_xml::EMU_PER_PIXEL
+    }
+
+    /// how many columns and rows the image covers, at least 1 each
+    pub fn cell_span(&self) -> (i64, i64) {
+        match self.to {
+            Some(to) => (
+                to.col.saturating_sub(self.from.col).saturating_add(1).max(1),
+                to.row.saturating_sub(self.from.row).saturating_add(1).max(1),
+            ),
+            None => (1, 1),
+        }
+    }
+}
+
+/// where a picture nested in group shapes sits, in EMU, relative to the
+/// top left corner of its outermost group
+///
+/// **depth**: how many groups the picture is nested in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GroupPosition {
+    pub x: i64,
+    pub y: i64,
+    pub cx: i64,
+    pub cy: i64,
+    pub depth: usize,
+}
+
+/// the non-visual properties of a picture, its `cNvPr`
+///
+/// **name**: the picture's name, e.g. `Picture 1`
+///
+/// **descr**: the alt text description
+///
+/// **hidden**: the picture, or a group it's nested in, is hidden
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImageProps {
+    pub name: Option<String>,
+    pub descr: Option<String>,
+    pub title: Option<String>,
+    pub hidden: bool,
+}
+
+/// where clicking a picture leads to, from `a:hlinkClick` in its `cNvPr`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Hyperlink {
+    /// an external url, e.g. `https://example.com`
+    External(String),
+    /// a location in the workbook, e.g. `Sheet2!A1`
+    Internal(String),
+}
+
+/// how much of the image is cut off each side, from `a:srcRect`,
+/// in 1/1000 of a percent of the image's size, e.g. `left: 25000` cuts off
+/// the left 25%. negative values pad the image instead
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Crop {
+    pub left: i64,
+    pub top: i64,
+    pub right: i64,
+    pub bottom: i64,
+}
+
+/// how the picture is shown compared to its original image file
+///
+/// **crop**: `None` if the picture isn't cropped
+///
+/// **rotation**: clockwise, in 1/60000 of a degree like `a:xfrm rot`
+///
+/// **flip_h**, **flip_v**: mirrored horizontally / vertically,
+/// flips are applied before the rotation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ImageTransform {
+    pub crop: Option<Crop>,
+    pub rotation: i64,
+    pub flip_h: bool,
+    pub flip_v: bool,
+}
+
+impl ImageTransform {
+    pub fn rotation_degrees(&self) -> f64 {
+        self.rotation as f64 / 60_000.0
+    }
+
+    /// the picture is shown exactly as its image file
+    pub fn is_identity(&self) -> bool {
+        *self == ImageTransform::default()
+    }
+}
+
+/// how a picture fills a shape, from the `a:blipFill` of the shape
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FillMode {
+    /// stretched over the whole shape
+    Stretch,
+    /// repeated over the shape
+    Tile,
+}
+
+/// what kind of drawing object an image came from
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ImageKind {
+    /// a picture, `xdr:pic`
+    Picture,
+    /// a shape filled with a picture, `xdr:sp`
+    ///
+    /// **geometry**: the preset geometry of the shape, e.g. `roundRect` or
+    /// `ellipse`, `None` for a custom geometry
+    ShapeFill { geometry: Option<String>, fill_mode: FillMode },
+    /// the picture background of a cell comment, from a legacy vml drawing
+    CommentBackground,
+    /// a form control or picture in a legacy vml drawing
+    ///
+    /// **object_type**: `x:ClientData ObjectType`, e.g. `Button` or `Pict`
+    Control { object_type: Option<String> },
+    /// the preview picture of an embedded or linked ole object, e.g. a Word
+    /// document, a pdf or a Visio diagram
+    ///
+    /// **prog_id**: the application the object belongs to, e.g.
+    /// `Word.Document.12` or `AcroExch.Document.DC`
+    ///
+    /// **embedding**: the part name of the embedded binary, e.g.
+    /// `xl/embeddings/oleObject1.bin`, `None` for a linked object
+    OleObject { prog_id: Option<String>, embedding: Option<String> },
+}
+
+/// where an image's bytes live
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ImageSource {
+    /// a part inside the xlsx package, e.g. `xl/media/image1.png`
+    Embedded(String),
+    /// a file outside the package the picture links to, by its target uri,
+    /// e.g. `file:///C:/images/logo.png`
+    Linked(String),
+}
+
+/// a single image found in the xlsx file
+///
+/// **source**: the image's part name inside the xlsx package,
+/// or the uri of the file it links to
+///
+/// **path**: where the image was unzipped to, `None` unless unzipped to disk
+/// and found there.
+/// a linked image only gets a path from _ImgLoader::resolve_linked_images_
+///
+/// **anchor**: where the image sits on the sheet, and the kind of anchor
+/// it came from
+///
+/// **group_position**: where the image sits in its group shape, `None` if
+/// it's not grouped. a grouped image is reported with the group's cell
+///
+/// **props**: name, alt text, title and hidden flag of the picture
+///
+/// **hyperlink**: where clicking the picture leads to, if anywhere
+///
+/// **transform**: crop, rotation and flips Excel applies when showing the
+/// picture, the image file itself is left untouched
+///
+/// **svg_part_name**: part name of the svg original of an inserted svg,
+/// `source` is then the png fallback Excel renders it to
+///
+/// **kind**: whether the image is a picture or fills a shape
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XlsxImage {
+    pub source: ImageSource,
+    pub path: Option<PathBuf>,
+    pub anchor: ImageAnchor,
+    pub group_position: Option<GroupPosition>,
+    pub props: ImageProps,
+    pub hyperlink: Option<Hyperlink>,
+    pub transform: ImageTransform,
+    pub svg_part_name: Option<String>,
+    pub kind: ImageKind,
+}
+
+impl XlsxImage {
+    /// the image's part name inside the xlsx package, `None` if it's linked
+    pub fn part_name(&self) -> Option<&str> {
+        match &self.source {
+            ImageSource::Embedded(part_name) => Some(part_name),
+            ImageSource::Linked(_) => None,
+        }
+    }
+}
+
+/// left, center or right part of a header or footer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HeaderFooterPosition {
+    Left,
+    Center,
+    Right,
+}
+
+/// a page header or footer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HeaderFooterKind {
+    Header,
+    Footer,
+}
+
+/// the pages a header or footer is printed on, `First` and `Even` are only
+/// used when the sheet has a different first page or different odd and even
+/// pages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HeaderFooterPage {
+    First,
+    Odd,
+    Even,
+}
+
+/// where a header/footer image is printed, e.g. the left header of odd pages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HeaderFooterSection {
+    pub position: HeaderFooterPosition,
+    pub kind: HeaderFooterKind,
+    pub page: HeaderFooterPage,
+}
+
+/// an image printed in a page header or footer, placed by a `&G` code
+///
+/// **path**: where the image was unzipped to, `None` unless unzipped to disk
+/// and found there
+///
+/// **title**: the image's title, usually its original file name
+///
+/// **width**, **height**: size in EMU, 0 when unknown
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderFooterImage {
+    pub section: HeaderFooterSection,
+    pub source: ImageSource,
+    pub path: Option<PathBuf>,
+    pub title: Option<String>,
+    pub width: i64,
+    pub height: i64,
+}
+
+/// the picture tiled behind a worksheet, from its `<picture r:id>`
+///
+/// **path**: where the image was unzipped to, `None` unless unzipped to disk
+/// and found there
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackgroundImage {
+    pub source: ImageSource,
+    pub path: Option<PathBuf>,
+}
+
+/// what a relationship points at, from the last segment of its `Type`,
+/// e.g. `.../relationships/drawing`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RelationshipKind {
+    /// the package's main part, in `_rels/.rels`: the workbook
+    OfficeDocument,
+    Drawing,
+    VmlDrawing,
+    Image,
+    Hyperlink,
+    Table,
+    Comments,
+    ThreadedComments,
+    PivotTable,
+    PrinterSettings,
+    OleObject,
+    Package,
+    Control,
+    /// any other type, by its full `Type` uri
+    Other(String),
+}
+
+impl RelationshipKind {
+    /// the kind of a relationship `Type` uri, the same for transitional and
+    /// strict uris
+    pub fn from_type(rel_type: &str) -> Self {
+        match rel_type.rsplit('/').next().unwrap_or(rel_type) {
+            "officeDocument" => RelationshipKind::OfficeDocument,
+            "drawing" => RelationshipKind::Drawing,
+            "vmlDrawing" => RelationshipKind::VmlDrawing,
+            "image" => RelationshipKind::Image,
+            "hyperlink" => RelationshipKind::Hyperlink,
+            "table" => RelationshipKind::Table,
+            "comments" => RelationshipKind::Comments,
+            "threadedComment" => RelationshipKind::ThreadedComments,
+            "pivotTable" => RelationshipKind::PivotTable,
+            "printerSettings" => RelationshipKind::PrinterSettings,
+            "oleObject" => RelationshipKind::OleObject,
+            "package" => RelationshipKind::Package,
+            "control" => RelationshipKind::Control,
+            _ => RelationshipKind::Other(rel_type.to_owned()),
+        }
+    }
+}
+
+/// a `Relationship` in a .rels part
+///
+/// **rel_type**: the full `Type` uri
+///
+/// **target**: the `Target` as written, relative to the source part
+///
+/// **part_name**: the target's part name, `None` for an external target,
+/// a location like `#Sheet2!A1` or a target missing from the package
+///
+/// **external**: `TargetMode="External"`, the target is a uri outside the
+/// package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relationship {
+    pub kind: RelationshipKind,
+    pub rel_type: String,
+    pub target: String,
+    pub part_name: Option<String>,
+    pub external: bool,
+}
+
+impl Relationship {
+    /// read the relationship as the target of a hyperlink, internal targets
+    /// are workbook locations like `#Sheet2!A1`
+    pub fn to_hyperlink(&self) -> Hyperlink {
+        if self.external {
+            Hyperlink::External(self.target.clone())
+        } else {
+            Hyperlink::Internal(
+                self.target
+                    .strip_prefix('#')
+                    .unwrap_or(&self.target)
+                    .to_owned(),
+            )
+        }
+    }
+
+    /// read the relationship as where an image's bytes live, `None` for an
+    /// internal target that is not a part
+    pub fn to_image_source(&self) -> Option<ImageSource> {
+        if self.external {
+            Some(ImageSource::Linked(self.target.clone()))
+        } else {
+            self.part_name.clone().map(ImageSource::Embedded)
+        }
+    }
+}
+
+/// every relationship of a worksheet, from its .rels part
+///
+/// **worksheet**: the worksheet's part name, e.g. `xl/worksheets/sheet1.xml`
+///
+/// **relationships**: a map of {relationship id: relationship}
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SheetRelationships {
+    pub worksheet: String,
+    pub relationships: HashMap<String, Relationship>,
+}
+
+impl SheetRelationships {
+    pub fn get(&self, r_id: &str) -> Option<&Relationship> {
+        self.relationships.get(r_id)
+    }
+
+    /// every relationship of the given kind, in no particular order
+    pub fn of_kind<'a>(
+        &'a self,
+        kind: &'a RelationshipKind,
+    ) -> impl Iterator<Item = &'a Relationship> + 'a {
+        self.relationships.values().filter(move |rel| &rel.kind == kind)
+    }
+}
+
+/// which branch of an `mc:AlternateContent` is read, a `mc:Choice` is only
+/// taken when every namespace in its `Requires` is supported
+///
+/// **PreferChoice**: the first supported Choice, else the Fallback, as a
+/// current Excel reads it
+///
+/// **PreferFallback**: the Fallback, else the first supported Choice, as an
+/// older consumer reads it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum AlternateContentPolicy {
+    #[default]
+    PreferChoice,
+    PreferFallback,
+}
+
+/// options for loading a workbook, `LoadOptions::default()` keeps every
+/// image
+///
+/// **exclude_hidden**: leave out hidden pictures
+///
+/// **alternate_content**: which `mc:AlternateContent` branch to read
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    pub exclude_hidden: bool,
+    pub alternate_content: AlternateContentPolicy,
+}
+
+/// the SpreadsheetML package variant, detected from the content type of
+/// the workbook part in `[Content_Types].xml`, not from the file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpreadsheetFormat {
+    /// a workbook, `.xlsx`
+    Xlsx,
+    /// a macro-enabled workbook, `.xlsm`
+    Xlsm,
+    /// a template, `.xltx`
+    Xltx,
+    /// a macro-enabled template, `.xltm`
+    Xltm,
+    /// an add-in, `.xlam`
+    Xlam,
+}
+
+impl SpreadsheetFormat {
+    /// the variant of a workbook part content type, `None` if it isn't one.
+    /// media types are compared case-insensitively
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        [
+            (
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml",
+                SpreadsheetFormat::Xlsx,
+            ),
+            (
+                "application/vnd.ms-excel.sheet.macroEnabled.main+xml",
+                SpreadsheetFormat::Xlsm,
+            ),
+            (
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.template.main+xml",
+                SpreadsheetFormat::Xltx,
+            ),
+            (
+                "application/vnd.ms-excel.template.macroEnabled.main+xml",
+                SpreadsheetFormat::Xltm,
+            ),
+            (
+                "application/vnd.ms-excel.addin.macroEnabled.main+xml",
+                SpreadsheetFormat::Xlam,
+            ),
+        ]
+        .into_iter()
+        .find(|(workbook_type, _)| {
+            workbook_type.eq_ignore_ascii_case(content_type.trim())
+        })
+        .map(|(_, format)| format)
+    }
+
+    /// the usual file extension, e.g. `xlsm`
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SpreadsheetFormat::Xlsx => "xlsx",
+            SpreadsheetFormat::Xlsm => "xlsm",
+            SpreadsheetFormat::Xltx => "xltx",
+            SpreadsheetFormat::Xltm => "xltm",
+            SpreadsheetFormat::Xlam => "xlam",
+        }
+    }
+}
 
 /// main struct to contain the retrieved info
 ///
 ///
-/// **xlsx_path**: the given xlsx file path, parsed from user input
+/// **xlsx_path**: the given xlsx file path, parsed from user input,
+/// `None` when loaded from a reader or bytes
+///
+/// **unzip_dir**: the dir the xlsx file was unzipped to, `None` when
+/// loaded in memory with _ImgLoader::open_
+///
+/// **format**: the package variant, e.g. `SpreadsheetFormat::Xlsm`
+///
+/// **worksheet_name_id_map**: a map of {sheet_id: sheetname}
+///
+/// **worksheet_name_img_map**: a full map of {sheetname: {(col, row): images}}
 ///
-/// **worksheet_name_id_map**: a map of {sheetname: sheet_id}
+/// **worksheet_id_img_map**: a full map of {sheet_id: {(col, row): images}}
 ///
-/// **worksheet_name_img_map**: a full map of {sheetname: {(col, row): imgpath}}
+/// **worksheet_name_header_footer_img_map**: a map of
+/// {sheetname: header and footer images}
 ///
-/// 
+/// **worksheet_name_background_img_map**: a map of
+/// {sheetname: background image}
+///
+/// **worksheet_name_relationships_map**: a map of
+/// {sheetname: every relationship of the sheet}, for every sheet whose
+/// `r:id` leads to a worksheet part, a sheet with a dangling target is
+/// skipped
 #[derive(Debug)]
-pub struct ImgLoader {
-    pub xlsx_path: XlsxPath,
-    pub unzip_dir: PathBuf,
+pub struct ImgLoader<R = File> {
+    pub xlsx_path: Option<XlsxPath>,
+    pub unzip_dir: Option<PathBuf>,
+    pub format: SpreadsheetFormat,
     pub worksheet_name_id_map: HashMap<i64, String>,
     pub worksheet_name_img_map:
-        HashMap<String, HashMap<(i64, i64), Vec<PathBuf>>>,
-    pub worksheet_id_img_map: HashMap<i64, HashMap<(i64, i64), Vec<PathBuf>>>,
-}
-// impl Drop for ImgLoader {
-//     fn drop(&mut self) {
-//         match std::fs::remove_dir_all(&self.unzip_dir) {
-//             Ok(_) => {}
-//             Err(_) => {}
-//         }
-//     }
-// }
+        HashMap<String, HashMap<(i64, i64), Vec<XlsxImage>>>,
+    pub worksheet_id_img_map:
+        HashMap<i64, HashMap<(i64, i64), Vec<XlsxImage>>>,
+    pub worksheet_name_header_footer_img_map:
+        HashMap<String, Vec<HeaderFooterImage>>,
+    pub worksheet_name_background_img_map: HashMap<String, BackgroundImage>,
+    pub worksheet_name_relationships_map: HashMap<String, SheetRelationships>,
+    /// behind a mutex rather than a `RefCell` so a loader can be shared
+    /// between threads, reads of image bytes from the archive take turns
+    archive: Mutex<XlsxArchive<R>>,
+}
 
 impl ImgLoader {
-    /// construct a new ImgLoader
+    /// construct a new ImgLoader, unzipping the xlsx file into `unzip_dir`
     ///
-    /// note: a temp/ dir will be created in the current dir
-    pub fn new(xlsx_path: &XlsxPath, unzip_dir: &str) -> Result<Option<Self>, IoError> {
+    /// note: `unzip_dir` will be created if it does not exist
+    pub fn new(xlsx_path: &XlsxPath, unzip_dir: &str) -> Result<Self, Error> {
+        Self::new_with_options(xlsx_path, unzip_dir, &LoadOptions::default())
+    }
+
+    /// same as _ImgLoader::new_, with the given options
+    pub fn new_with_options(
+        xlsx_path: &XlsxPath,
+        unzip_dir: &str,
+        options: &LoadOptions,
+    ) -> Result<Self, Error> {
         let temp_dir = Path::new(unzip_dir);
         if !temp_dir.exists() {
             if let Err(e) = std::fs::create_dir_all(temp_dir) {
@@ -51,103 +625,539 @@ impl ImgLoader {
                         temp_dir.display()
                     ),
                     source: e,
-                });
+                }
+                .into());
             }
         }
 
-        match unzip_utils::unzip_xlsx(&xlsx_path, temp_dir) {
-            Err(e) => {return Err(e)},
-            Ok(None) => {Ok(None)}
-            Ok(Some(UnzippedPaths {
-                unzip_dir,
-                media_dir,
-                workbook_xml,
-                drawing_dir,
-                drawing_rels_dir,
-                worksheet_rels_dir,
-            })) => {
-                let mut worksheet_name_img_map = HashMap::new();
-                let mut worksheet_id_img_map = HashMap::new();
-                // parse workbook_xml, get worksheet names and ids
-                let worksheet_name_id_map =
-                    parse_xml::get_worksheet_name_id_map(
-                        &workbook_xml.as_path(),
-                    );
+        let mut loader = Self::open_with_options(xlsx_path, options)?;
+        let xlsx_file = xlsx_path.as_pathbuf();
+        let file_stem =
+            xlsx_file.file_stem().and_then(OsStr::to_str).unwrap_or("xlsx");
+        let unzip_dir = unzip_utils::unzip_xlsx(
+            loader.archive.get_mut().unwrap_or_else(PoisonError::into_inner),
+            temp_dir,
+            file_stem,
+        )?;
+
+        // an image that is not found among the unzipped files keeps no path
+        // rather than failing the load
+        for img in loader
+            .worksheet_name_img_map
+            .values_mut()
+            .chain(loader.worksheet_id_img_map.values_mut())
+            .flat_map(|col_row_img_dict| col_row_img_dict.values_mut())
+            .flatten()
+        {
+            if let Some(part_name) = img.part_name() {
+                img.path =
+                    parse_xml::compute_abs_img_path(part_name, &unzip_dir)
+                        .ok();
+            }
+        }
+        for img in
+            loader.worksheet_name_header_footer_img_map.values_mut().flatten()
+        {
+            if let ImageSource::Embedded(part_name) = &img.source {
+                img.path =
+                    parse_xml::compute_abs_img_path(part_name, &unzip_dir)
+                        .ok();
+            }
+        }
+        for img in loader.worksheet_name_background_img_map.values_mut() {
+            if let ImageSource::Embedded(part_name) = &img.source {
+                img.path =
+                    parse_xml::compute_abs_img_path(part_name, &unzip_dir)
+                        .ok();
+            }
+        }
+        loader.unzip_dir = Some(unzip_dir);
+
+        Ok(loader)
+    }
+
+    /// construct a new ImgLoader without unzipping anything to disk
+    ///
+    /// xml parts are read straight from the archive, image bytes are only
+    /// read when asked for with _ImgLoader::read_img_bytes_
+    pub fn open(xlsx_path: &XlsxPath) -> Result<Self, Error> {
+        Self::open_with_options(xlsx_path, &LoadOptions::default())
+    }
+
+    /// same as _ImgLoader::open_, with the given options
+    pub fn open_with_options(
+        xlsx_path: &XlsxPath,
+        options: &LoadOptions,
+    ) -> Result<Self, Error> {
+        let file = File::open(xlsx_path.as_pathbuf())?;
+        let loader = Self::from_reader_with_options(file, options)?;
+
+        Ok(ImgLoader { xlsx_path: Some(xlsx_path.clone()), ..loader })
+    }
+}
+
+impl<'a> ImgLoader<Cursor<&'a [u8]>> {
+    /// construct a new ImgLoader from the bytes of an xlsx file,
+    /// e.g. an http upload body
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, Error> {
+        Self::from_reader(Cursor::new(bytes))
+    }
+
+    /// same as _ImgLoader::from_bytes_, with the given options
+    pub fn from_bytes_with_options(
+        bytes: &'a [u8],
+        options: &LoadOptions,
+    ) -> Result<Self, Error> {
+        Self::from_reader_with_options(Cursor::new(bytes), options)
+    }
+}
+
+impl<R: Read + Seek> ImgLoader<R> {
+    /// construct a new ImgLoader from any `Read + Seek` source
+    ///
+    /// the source is checked to be a spreadsheet package by its zip signature
+    /// and `[Content_Types].xml`
+    ///
+    /// a valid workbook without any images gives a loader with empty maps,
+    /// an error is only returned when the workbook itself is invalid
+    pub fn from_reader(reader: R) -> Result<Self, Error> {
+        Self::from_reader_with_options(reader, &LoadOptions::default())
+    }
+
+    /// same as _ImgLoader::from_reader_, with the given options
+    pub fn from_reader_with_options(
+        reader: R,
+        options: &LoadOptions,
+    ) -> Result<Self, Error> {
+        let mut archive = XlsxArchive::new(reader)?;
+
+        let workbook_xml = archive.workbook_part_name().to_owned();
+        let workbook_rels = parse_xml::get_rels_part_name(&workbook_xml);
+        // WPS Office keeps pictures embedded in cells here
+        let cell_images_xml = "xl/cellimages.xml";
+        // Excel 365 keeps "Place in Cell" pictures as rich values
+        let metadata_xml = "xl/metadata.xml";
+        let rich_value_xml = "xl/richData/rdrichvalue.xml";
+        let rich_value_structure_xml = "xl/richData/rdrichvaluestructure.xml";
+        let rich_value_rel_xml = "xl/richData/richValueRel.xml";
+
+        let has_rich_values = archive.has_part(metadata_xml)
+            && archive.has_part(rich_value_xml)
+            && archive.has_part(rich_value_rel_xml);
+        for required_part in [&workbook_xml, &workbook_rels] {
+            if !archive.has_part(required_part) {
+                return Err(XlsxFormatError::MissingPart(
+                    required_part.clone(),
+                )
+                .into());
+            }
+        }
+
+        let mut worksheet_name_img_map = HashMap::new();
+        let mut worksheet_id_img_map = HashMap::new();
+        let mut worksheet_name_header_footer_img_map = HashMap::new();
+        let mut worksheet_name_background_img_map = HashMap::new();
+        let mut worksheet_name_relationships_map = HashMap::new();
+        // parse workbook_xml, get worksheet names and ids
+        let workbook_xml_str =
+            archive.read_part_string(&workbook_xml)?.ok_or_else(|| {
+                XlsxFormatError::MissingPart(workbook_xml.clone())
+            })?;
+        // each <sheet r:id> points to its worksheet part through workbook.xml.rels
+        let workbook_sheets =
+            parse_xml::get_workbook_sheets(&workbook_xml_str, &workbook_xml)?;
+        let worksheet_name_id_map = workbook_sheets
+            .iter()
+            .map(|(sheet_id, sheet_name, _)| (*sheet_id, sheet_name.clone()))
+            .collect();
+        let workbook_relationships =
+            archive.read_relationships(&workbook_xml)?;
+
+        // parse cellimages.xml and its rels, get a map of {image id: image part name}
+        let mut cell_img_dict = HashMap::new();
+        if let Some(cell_images_str) =
+            archive.read_part_string(cell_images_xml)?
+        {
+            let relationships = archive.read_relationships(cell_images_xml)?;
+            cell_img_dict = parse_xml::get_cell_img_dict(
+                &cell_images_str,
+                &relationships,
+                cell_images_xml,
+            )?;
+        }
+
+        // resolve vm -> metadata -> rich value -> richValueRel -> image,
+        // get a map of {vm: image}
+        let mut vm_img_dict = HashMap::new();
+        if has_rich_values {
+            let vm_rich_value_map = archive
+                .read_part_string(metadata_xml)?
+                .map(|xml| {
+                    parse_xml::get_vm_rich_value_map(&xml, metadata_xml)
+                })
+                .transpose()?
+                .unwrap_or_default();
+            let rich_value_structure_str =
+                archive.read_part_string(rich_value_structure_xml)?;
+            let rich_value_rel_indices = archive
+                .read_part_string(rich_value_xml)?
+                .map(|xml| {
+                    parse_xml::get_rich_value_rel_indices(
+                        &xml,
+                        rich_value_xml,
+                        rich_value_structure_str.as_deref(),
+                        rich_value_structure_xml,
+                    )
+                })
+                .transpose()?
+                .unwrap_or_default();
+            let rich_value_rel_rids = archive
+                .read_part_string(rich_value_rel_xml)?
+                .map(|xml| {
+                    parse_xml::get_rich_value_rel_rids(
+                        &xml,
+                        rich_value_rel_xml,
+                    )
+                })
+                .transpose()?
+                .unwrap_or_default();
+            let relationships =
+                archive.read_relationships(rich_value_rel_xml)?;
+
+            for (vm, rich_value_index) in vm_rich_value_map {
+                let part_name = rich_value_rel_indices
+                    .get(rich_value_index as usize)
+                    .copied()
+                    .flatten()
+                    .and_then(|rel_index| {
+                        rich_value_rel_rids.get(rel_index as usize)
+                    })
+                    .and_then(|r_id| relationships.get(r_id))
+                    .and_then(|rel| rel.part_name.clone());
+                if let Some(part_name) = part_name {
+                    let img = XlsxImage {
+                        source: ImageSource::Embedded(part_name),
+                        path: None,
+                        anchor: ImageAnchor::in_cell(0, 0),
+                        group_position: None,
+                        props: ImageProps::default(),
+                        hyperlink: None,
+                        transform: ImageTransform::default(),
+                        svg_part_name: None,
+                        kind: ImageKind::Picture,
+                    };
+                    vm_img_dict.insert(vm, img);
+                }
+            }
+        }
+
+        for (sheet_id, sheet_name, r_id) in workbook_sheets {
+            let Some(worksheet_xml) = workbook_relationships
+                .get(&r_id)
+                .and_then(|rel| rel.part_name.clone())
+            else {
+                continue;
+            };
+            let worksheet_xml_str =
+                archive.read_part_string(&worksheet_xml)?.ok_or_else(
+                    || ParseError::MissingPart { part: worksheet_xml.clone() },
+                )?;
+            let mut col_row_img_dict: HashMap<(i64, i64), Vec<XlsxImage>> =
+                HashMap::new();
 
-                // parse .rels files in worksheet_rels_dir, and get drawing xml and worksheet map
-                let sheet_and_drawing_xml_map =
-                    parse_xml::get_sheet_and_drawing_xml_map(
-                        &worksheet_rels_dir.as_path(),
-                        &drawing_dir.as_path(),
+            // every part the sheet relates to, images are found through it
+            let sheet_relationships = SheetRelationships {
+                worksheet: worksheet_xml.clone(),
+                relationships: archive.read_relationships(&worksheet_xml)?,
+            };
+
+            // the sheet is parsed once for everything its images are found by
+            let mut worksheet = parse_xml::get_worksheet(
+                &worksheet_xml_str,
+                &worksheet_xml,
+                &sheet_relationships,
+                options.alternate_content,
+            )?;
+            let sheet_dimensions = &worksheet.dimensions;
+
+            let drawing_xml = worksheet
+                .element_target("drawing", &sheet_relationships)
+                .or_else(|| {
+                    sheet_relationships
+                        .of_kind(&RelationshipKind::Drawing)
+                        .find_map(|rel| rel.part_name.clone())
+                });
+            if let Some(drawing_xml) = drawing_xml {
+                if let Some(drawing_xml_str) =
+                    archive.read_part_string(&drawing_xml)?
+                {
+                    let col_row_rid = parse_xml::get_col_row_r_id_sans_xdr(
+                        &drawing_xml_str,
+                        &drawing_xml,
+                        sheet_dimensions,
+                        options.alternate_content,
+                    )?;
+                    let relationships =
+                        archive.read_relationships(&drawing_xml)?;
+                    col_row_img_dict = parse_xml::generate_col_row_img_dict(
+                        col_row_rid,
+                        &relationships,
                     );
-                // dbg!(&sheet_and_drawing_xml_map);
-                for (sheet_id, sheet_name) in worksheet_name_id_map.clone() {
-                    let sheet_rels_filename =
-                        format!("sheet{sheet_id}.xml.rels");
-                    if let Some(drawing_xml) =
-                        sheet_and_drawing_xml_map.get(&sheet_rels_filename)
-                    {
-                        let drawing_xml_basename =
-                            drawing_xml.file_name().unwrap().to_str().unwrap();
-                        let drawing_rels_filename =
-                            format!("{drawing_xml_basename}.rels");
-                        let drawing_rels_filepath =
-                            drawing_rels_dir.join(drawing_rels_filename);
-
-                        // let col_row_rid = parse_xml::get_col_row_r_id(
-                        //     &drawing_xml.as_path(),
-                        // );
-
-                        let col_row_rid = parse_xml::get_col_row_r_id_sans_xdr(
-                            &drawing_xml.as_path(),
-                        );
-
-                        if let Ok(rid_img_dict) = parse_xml::get_rid_img_dict(
-                            &drawing_rels_filepath.as_path(),
-                        ) {
-                            // dbg!(&rid_img_dict);
-                            let col_row_abs_img_dict =
-                                parse_xml::generate_col_row_abs_img_dict(
-                                    col_row_rid,
-                                    rid_img_dict,
-                                    media_dir.as_path(),
-                                );
-
-                            worksheet_name_img_map.insert(
-                                sheet_name,
-                                col_row_abs_img_dict.clone(),
-                            );
-                            worksheet_id_img_map
-                                .insert(sheet_id, col_row_abs_img_dict);
-                        }
+                }
+            }
+
+            // a legacy vml drawing holds comment backgrounds and controls
+            let vml_drawing = worksheet
+                .element_target("legacyDrawing", &sheet_relationships);
+            if let Some(vml_drawing) = vml_drawing {
+                if let Some(vml_str) =
+                    archive.read_part_string(&vml_drawing)?
+                {
+                    let vml_img_ids = parse_xml::get_vml_img_ids(
+                        &vml_str,
+                        &vml_drawing,
+                        sheet_dimensions,
+                        &worksheet.ole_objects,
+                    )?;
+                    let relationships =
+                        archive.read_relationships(&vml_drawing)?;
+                    for (col_row, imgs) in parse_xml::generate_col_row_img_dict(
+                        vml_img_ids,
+                        &relationships,
+                    ) {
+                        col_row_img_dict
+                            .entry(col_row)
+                            .or_default()
+                            .extend(imgs);
                     }
                 }
-                Ok(Some(ImgLoader {
-                    xlsx_path: (*xlsx_path).clone(),
-                    unzip_dir,
-                    worksheet_name_id_map,
-                    worksheet_name_img_map,
-                    worksheet_id_img_map,
-                }))
+            }
+
+            // ole objects show a preview picture, placed by objectPr or by
+            // a shape in the legacy vml drawing
+            for (col_row, imgs) in parse_xml::generate_col_row_img_dict(
+                std::mem::take(&mut worksheet.ole_objects).into_img_ids(),
+                &sheet_relationships.relationships,
+            ) {
+                col_row_img_dict.entry(col_row).or_default().extend(imgs);
+            }
+
+            // header/footer images live in a vml drawing of their own,
+            // placed by &G codes in the sheet's headerFooter
+            let header_footer_vml = worksheet
+                .element_target("legacyDrawingHF", &sheet_relationships);
+            if let Some(header_footer_vml) = header_footer_vml {
+                if let Some(vml_str) =
+                    archive.read_part_string(&header_footer_vml)?
+                {
+                    let relationships =
+                        archive.read_relationships(&header_footer_vml)?;
+                    let header_footer_imgs =
+                        parse_xml::get_header_footer_imgs(
+                            &vml_str,
+                            &header_footer_vml,
+                            &relationships,
+                            &worksheet.header_footer_placeholders,
+                        )?;
+                    if !header_footer_imgs.is_empty() {
+                        worksheet_name_header_footer_img_map
+                            .insert(sheet_name.clone(), header_footer_imgs);
+                    }
+                }
+            }
+
+            // <picture r:id> is the tiled sheet background
+            if let Some(background) =
+                worksheet.element_target("picture", &sheet_relationships)
+            {
+                worksheet_name_background_img_map.insert(
+                    sheet_name.clone(),
+                    BackgroundImage {
+                        source: ImageSource::Embedded(background),
+                        path: None,
+                    },
+                );
+            }
+
+            // cells with =DISPIMG("ID_xxx",1) formulas point to WPS cell images
+            if !cell_img_dict.is_empty() {
+                for (col_row, img_id) in &worksheet.dispimg_cells {
+                    if let Some(img) = cell_img_dict.get(img_id) {
+                        let mut img = img.clone();
+                        img.anchor.from =
+                            AnchorMarker::at_cell(col_row.0, col_row.1);
+                        col_row_img_dict
+                            .entry(*col_row)
+                            .or_default()
+                            .push(img);
+                    }
+                }
+            }
+
+            // cells with a vm="..." attribute may hold a rich value image
+            if !vm_img_dict.is_empty() {
+                for (col_row, vm) in &worksheet.vm_cells {
+                    if let Some(img) = vm_img_dict.get(vm) {
+                        let mut img = img.clone();
+                        img.anchor.from =
+                            AnchorMarker::at_cell(col_row.0, col_row.1);
+                        col_row_img_dict
+                            .entry(*col_row)
+                            .or_default()
+                            .push(img);
+                    }
+                }
+            }
+
+            worksheet_name_relationships_map
+                .insert(sheet_name.clone(), sheet_relationships);
+
+            if options.exclude_hidden {
+                for imgs in col_row_img_dict.values_mut() {
+                    imgs.retain(|img| !img.props.hidden);
+                }
+                col_row_img_dict.retain(|_, imgs| !imgs.is_empty());
+            }
+
+            if !col_row_img_dict.is_empty() {
+                worksheet_name_img_map
+                    .insert(sheet_name, col_row_img_dict.clone());
+                worksheet_id_img_map.insert(sheet_id, col_row_img_dict);
             }
         }
+        Ok(ImgLoader {
+            xlsx_path: None,
+            unzip_dir: None,
+            format: archive.format(),
+            worksheet_name_id_map,
+            worksheet_name_img_map,
+            worksheet_id_img_map,
+            worksheet_name_header_footer_img_map,
+            worksheet_name_background_img_map,
+            worksheet_name_relationships_map,
+            archive: Mutex::new(archive),
+        })
+    }
+
+    /// a read that panicked can't leave the archive half updated, so a
+    /// poisoned lock is taken over
+    fn lock_archive(&self) -> MutexGuard<'_, XlsxArchive<R>> {
+        self.archive.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// read an image's bytes from the xlsx archive, a linked image is read
+    /// from the path it was resolved to
+    pub fn read_img_bytes(&self, img: &XlsxImage) -> Result<Vec<u8>, Error> {
+        self.read_source_bytes(&img.source, img.path.as_deref())
     }
-}
 
-/// get file extension as string lowercase
-fn get_file_ext_lower<S>(filepath: S) -> String
-where
-    S: AsRef<Path>,
-{
-    filepath
-        .as_ref()
-        .extension()
-        .and_then(OsStr::to_str)
-        .map_or(String::new(), str::to_lowercase)
+    /// read a header/footer image's bytes, like _ImgLoader::read_img_bytes_
+    pub fn read_header_footer_img_bytes(
+        &self,
+        img: &HeaderFooterImage,
+    ) -> Result<Vec<u8>, Error> {
+        self.read_source_bytes(&img.source, img.path.as_deref())
+    }
+
+    /// read a sheet background's bytes, like _ImgLoader::read_img_bytes_
+    pub fn read_background_img_bytes(
+        &self,
+        img: &BackgroundImage,
+    ) -> Result<Vec<u8>, Error> {
+        self.read_source_bytes(&img.source, img.path.as_deref())
+    }
+
+    fn read_source_bytes(
+        &self,
+        source: &ImageSource,
+        path: Option<&Path>,
+    ) -> Result<Vec<u8>, Error> {
+        match source {
+            ImageSource::Embedded(part_name) => self
+                .lock_archive()
+                .read_part_bytes(part_name)?
+                .ok_or_else(|| {
+                    ParseError::MissingPart { part: part_name.clone() }.into()
+                }),
+            ImageSource::Linked(uri) => match path {
+                Some(path) => Ok(std::fs::read(path)?),
+                None => Err(Error::UnresolvedLink(uri.clone())),
+            },
+        }
+    }
+
+    /// read the svg original of an image, `None` if it has none
+    pub fn read_svg_bytes(
+        &self,
+        img: &XlsxImage,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let Some(part_name) = &img.svg_part_name else {
+            return Ok(None);
+        };
+        self.lock_archive()
+            .read_part_bytes(part_name)?
+            .ok_or_else(|| {
+                ParseError::MissingPart { part: part_name.clone() }.into()
+            })
+            .map(Some)
+    }
+
+    /// read an image's bytes with its crop, rotation and flips applied,
+    /// re-encoded as png. an image without any transform is returned as is
+    #[cfg(feature = "transform")]
+    pub fn read_transformed_img_bytes(
+        &self,
+        img: &XlsxImage,
+    ) -> Result<Vec<u8>, Error> {
+        let bytes = self.read_img_bytes(img)?;
+        if img.transform.is_identity() {
+            return Ok(bytes);
+        }
+        transform::apply_transform(&bytes, &img.transform)
+    }
+
+    /// map every linked image to a local file, e.g. after downloading it,
+    /// `resolver` gets the link's target uri and returns `None` to leave the
+    /// image unresolved. it's called once per distinct uri, the same image
+    /// is kept both by sheet name and by sheet id
+    pub fn resolve_linked_images<F>(&mut self, mut resolver: F)
+    where
+        F: FnMut(&str) -> Option<PathBuf>,
+    {
+        let mut resolved: HashMap<String, Option<PathBuf>> = HashMap::new();
+        let mut resolve =
+            |source: &ImageSource, path: &mut Option<PathBuf>| {
+                if let ImageSource::Linked(uri) = source {
+                    *path = resolved
+                        .entry(uri.clone())
+                        .or_insert_with(|| resolver(uri))
+                        .clone();
+                }
+            };
+        for img in self
+            .worksheet_name_img_map
+            .values_mut()
+            .chain(self.worksheet_id_img_map.values_mut())
+            .flat_map(|col_row_img_dict| col_row_img_dict.values_mut())
+            .flatten()
+        {
+            resolve(&img.source, &mut img.path);
+        }
+        for img in
+            self.worksheet_name_header_footer_img_map.values_mut().flatten()
+        {
+            resolve(&img.source, &mut img.path);
+        }
+        for img in self.worksheet_name_background_img_map.values_mut() {
+            resolve(&img.source, &mut img.path);
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
-/// a NewType containing a string ended with .xlsx
+/// a NewType containing the path of an existing file, any SpreadsheetML
+/// package: xlsx, xlsm, xltx, xltm or xlam. the format is checked by its
+/// content when the file is opened, not by its extension
 pub struct XlsxPath(String);
 
 impl fmt::Display for XlsxPath {
@@ -171,20 +1181,125 @@ impl FromStr for XlsxPath {
     type Err = XlsxPathParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let p = Path::new(s);
-        if !p.exists() {
+        if !Path::new(s).exists() {
             Err(XlsxPathParseError::FileNotFound(s.to_owned()))
         } else {
-            let ext = get_file_ext_lower(p);
-
-            if ext != "xlsx" {
-                Err(XlsxPathParseError::InvalidFormat {
-                    expected: "xlsx".to_owned(),
-                    found: ext,
-                })
-            } else {
-                Ok(XlsxPath(s.to_owned()))
-            }
+            Ok(XlsxPath(s.to_owned()))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::tests::{
+        content_types, package_bytes, rels, WORKBOOK,
+    };
+
+    const MAIN: &str =
+        "http://schemas.openxmlformats.org/spreadsheetml/2006/main";
+    const REL: &str =
+        "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+
+    /// a workbook of one sheet, `Sheet1`, with `sheet_body` in its
+    /// worksheet and `parts` added to the package
+    fn workbook(sheet_body: &str, parts: &[(&str, &str)]) -> Vec<u8> {
+        let content_types = content_types(&[("/xl/workbook.xml", WORKBOOK)]);
+        let package_rels =
+            rels(&[("rId1", "officeDocument", "xl/workbook.xml")]);
+        let workbook_xml = format!(
+            r#"<workbook xmlns="{MAIN}" xmlns:r="{REL}"><sheets><sheet name="Sheet1" sheetId="1" r:id="rId1"/></sheets></workbook>"#
+        );
+        let workbook_rels =
+            rels(&[("rId1", "worksheet", "worksheets/sheet1.xml")]);
+        let sheet_xml = format!(
+            r#"<worksheet xmlns="{MAIN}" xmlns:r="{REL}"><sheetData/>{sheet_body}</worksheet>"#
+        );
+        let mut all_parts = vec![
+            ("[Content_Types].xml", content_types.as_str()),
+            ("_rels/.rels", &package_rels),
+            ("xl/workbook.xml", &workbook_xml),
+            ("xl/_rels/workbook.xml.rels", &workbook_rels),
+            ("xl/worksheets/sheet1.xml", &sheet_xml),
+        ];
+        all_parts.extend_from_slice(parts);
+        package_bytes(&all_parts)
+    }
+
+    /// a picture of `xl/media/image1.png` anchored from B3 to D6
+    const DRAWING_XML: &str = r#"<xdr:wsDr
+        xmlns:xdr="http://schemas.openxmlformats.org/drawingml/2006/spreadsheetDrawing"
+        xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main"
+        xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
+        <xdr:twoCellAnchor>
+            <xdr:from><xdr:col>1</xdr:col><xdr:colOff>0</xdr:colOff><xdr:row>2</xdr:row><xdr:rowOff>0</xdr:rowOff></xdr:from>
+            <xdr:to><xdr:col>3</xdr:col><xdr:colOff>0</xdr:colOff><xdr:row>5</xdr:row><xdr:rowOff>0</xdr:rowOff></xdr:to>
+            <xdr:pic>
+                <xdr:nvPicPr><xdr:cNvPr id="2" name="Picture 1"/><xdr:cNvPicPr/></xdr:nvPicPr>
+                <xdr:blipFill><a:blip r:embed="rId1"/></xdr:blipFill>
+                <xdr:spPr/>
+            </xdr:pic>
+            <xdr:clientData/>
+        </xdr:twoCellAnchor>
+    </xdr:wsDr>"#;
+
+    #[test]
+    fn from_bytes_maps_drawing_pictures_to_cells() {
+        let sheet_rels =
+            rels(&[("rId1", "drawing", "../drawings/drawing1.xml")]);
+        let drawing_rels = rels(&[("rId1", "image", "../media/image1.png")]);
+        let bytes = workbook(
+            r#"<drawing r:id="rId1"/>"#,
+            &[
+                ("xl/worksheets/_rels/sheet1.xml.rels", &sheet_rels),
+                ("xl/drawings/drawing1.xml", DRAWING_XML),
+                ("xl/drawings/_rels/drawing1.xml.rels", &drawing_rels),
+                ("xl/media/image1.png", "png bytes"),
+            ],
+        );
+        let loader = ImgLoader::from_bytes(&bytes).unwrap();
+        assert_eq!(loader.format, SpreadsheetFormat::Xlsx);
+        assert_eq!(
+            loader.worksheet_name_id_map,
+            HashMap::from([(1, "Sheet1".to_owned())])
+        );
+        let col_row_imgs = &loader.worksheet_name_img_map["Sheet1"];
+        assert_eq!(col_row_imgs.keys().collect::<Vec<_>>(), [&(2, 3)]);
+        let img = &col_row_imgs[&(2, 3)][0];
+        assert_eq!(img.part_name(), Some("xl/media/image1.png"));
+        assert_eq!(img.anchor.to, Some(AnchorMarker::at_cell(4, 6)));
+        assert_eq!(img.props.name.as_deref(), Some("Picture 1"));
+        assert_eq!(loader.read_img_bytes(img).unwrap(), b"png bytes");
+        assert_eq!(loader.worksheet_id_img_map[&1], *col_row_imgs);
+    }
+
+    #[test]
+    fn workbook_without_images_has_empty_maps() {
+        let bytes = workbook("", &[]);
+        let loader = ImgLoader::from_bytes(&bytes).unwrap();
+        assert_eq!(loader.worksheet_name_id_map.len(), 1);
+        assert!(loader.worksheet_name_img_map.is_empty());
+        assert!(loader.worksheet_id_img_map.is_empty());
+        assert!(loader.worksheet_name_header_footer_img_map.is_empty());
+        assert!(loader.worksheet_name_background_img_map.is_empty());
+    }
+
+    #[test]
+    fn workbook_part_missing() {
+        let bytes = package_bytes(&[
+            (
+                "[Content_Types].xml",
+                &content_types(&[("/xl/workbook.xml", WORKBOOK)]),
+            ),
+            (
+                "_rels/.rels",
+                &rels(&[("rId1", "officeDocument", "xl/workbook.xml")]),
+            ),
+        ]);
+        assert!(matches!(
+            ImgLoader::from_bytes(&bytes),
+            Err(Error::XlsxFormat(XlsxFormatError::MissingPart(part)))
+                if part == "xl/workbook.xml"
+        ));
+    }
+}
@@ -0,0 +1,164 @@
+use std::io::Cursor;
+
+use image::imageops;
+use image::{DynamicImage, ImageOutputFormat, Rgba, RgbaImage};
+
+use super::errors::Error;
+use super::structs::{Crop, ImageTransform};
+
+/// `a:srcRect` values are in 1/1000 of a percent
+const CROP_UNITS: f64 = 100_000.0;
+/// `a:xfrm rot` is in 1/60000 of a degree
+const ROTATION_UNITS: i64 = 60_000;
+
+/// decode an image, apply its crop, flips and rotation in the order Excel
+/// does, and encode the result as png
+pub fn apply_transform(
+    bytes: &[u8],
+    transform: &ImageTransform,
+) -> Result<Vec<u8>, Error> {
+    let mut img = image::load_from_memory(bytes)?.to_rgba8();
+
+    if let Some(crop) = &transform.crop {
+        img = crop_image(&img, crop);
+    }
+    if transform.flip_h {
+        imageops::flip_horizontal_in_place(&mut img);
+    }
+    if transform.flip_v {
+        imageops::flip_vertical_in_place(&mut img);
+    }
+    img = rotate_image(&img, transform.rotation);
+
+    let mut buf = Cursor::new(Vec::new());
+    DynamicImage::ImageRgba8(img)
+        .write_to(&mut buf, ImageOutputFormat::Png)?;
+    Ok(buf.into_inner())
+}
+
+/// cut off each side, negative values (padding) are ignored
+fn crop_image(img: &RgbaImage, crop: &Crop) -> RgbaImage {
+    let (width, height) = (img.width() as f64, img.height() as f64);
+    let fraction = |v: i64| (v.max(0) as f64 / CROP_UNITS).min(1.0);
+    let left = (width * fraction(crop.left)).round() as u32;
+    let top = (height * fraction(crop.top)).round() as u32;
+    let right = (width * fraction(crop.right)).round() as u32;
+    let bottom = (height * fraction(crop.bottom)).round() as u32;
+    let new_width = img.width().saturating_sub(left + right).max(1);
+    let new_height = img.height().saturating_sub(top + bottom).max(1);
+    imageops::crop_imm(
+        img,
+        left.min(img.width() - 1),
+        top.min(img.height() - 1),
+        new_width,
+        new_height,
+    )
+    .to_image()
+}
+
+/// rotate clockwise around the center, `rotation` in 1/60000 of a degree.
+/// the canvas grows to fit the rotated image and the uncovered corners are
+/// transparent
+fn rotate_image(img: &RgbaImage, rotation: i64) -> RgbaImage {
+    let rotation = rotation.rem_euclid(360 * ROTATION_UNITS);
+    match rotation / ROTATION_UNITS {
+        _ if rotation % (90 * ROTATION_UNITS) != 0 => {}
+        0 => return img.clone(),
+        90 => return imageops::rotate90(img),
+        180 => return imageops::rotate180(img),
+        _ => return imageops::rotate270(img),
+    }
+
+    let degrees = rotation as f64 / ROTATION_UNITS as f64;
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (width, height) = (img.width() as f64, img.height() as f64);
+    let new_width = (width * cos.abs() + height * sin.abs()).ceil();
+    let new_height = (width * sin.abs() + height * cos.abs()).ceil();
+    let (cx, cy) = (width / 2.0, height / 2.0);
+    let (new_cx, new_cy) = (new_width / 2.0, new_height / 2.0);
+
+    // nearest neighbour, map each target pixel back into the source
+    RgbaImage::from_fn(new_width as u32, new_height as u32, |x, y| {
+        let dx = x as f64 + 0.5 - new_cx;
+        let dy = y as f64 + 0.5 - new_cy;
+        let src_x = dx * cos + dy * sin + cx;
+        let src_y = -dx * sin + dy * cos + cy;
+        if src_x < 0.0 || src_y < 0.0 || src_x >= width || src_y >= height {
+            Rgba([0, 0, 0, 0])
+        } else {
+            *img.get_pixel(src_x as u32, src_y as u32)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+    const CLEAR: Rgba<u8> = Rgba([0, 0, 0, 0]);
+
+    /// a `width` x `height` blue image with a red top left pixel
+    fn image(width: u32, height: u32) -> RgbaImage {
+        let mut img = RgbaImage::from_pixel(width, height, BLUE);
+        img.put_pixel(0, 0, RED);
+        img
+    }
+
+    #[test]
+    fn crop_cuts_off_each_side() {
+        let crop =
+            Crop { left: 25_000, top: 0, right: 25_000, bottom: 50_000 };
+        let mut img = image(4, 2);
+        img.put_pixel(1, 0, RED);
+        let cropped = crop_image(&img, &crop);
+        assert_eq!(cropped.dimensions(), (2, 1));
+        assert_eq!(*cropped.get_pixel(0, 0), RED);
+        assert_eq!(*cropped.get_pixel(1, 0), BLUE);
+        // padding is ignored
+        let padded = Crop { left: -25_000, ..Crop::default() };
+        assert_eq!(crop_image(&img, &padded), img);
+    }
+
+    #[test]
+    fn rotate_by_right_angles() {
+        let img = image(3, 2);
+        // clockwise, the top left corner ends up top right
+        let rotated = rotate_image(&img, 90 * ROTATION_UNITS);
+        assert_eq!(rotated.dimensions(), (2, 3));
+        assert_eq!(*rotated.get_pixel(1, 0), RED);
+        assert_eq!(rotate_image(&img, -270 * ROTATION_UNITS), rotated);
+        assert_eq!(rotate_image(&img, 360 * ROTATION_UNITS), img);
+        let upside_down = rotate_image(&img, 180 * ROTATION_UNITS);
+        assert_eq!(*upside_down.get_pixel(2, 1), RED);
+    }
+
+    #[test]
+    fn rotate_by_any_angle_grows_the_canvas() {
+        let img = RgbaImage::from_pixel(10, 10, BLUE);
+        let rotated = rotate_image(&img, 45 * ROTATION_UNITS);
+        // the diagonal of a 10px square is 14.14px
+        assert_eq!(rotated.dimensions(), (15, 15));
+        assert_eq!(*rotated.get_pixel(0, 0), CLEAR);
+        assert_eq!(*rotated.get_pixel(7, 7), BLUE);
+    }
+
+    #[test]
+    fn apply_transform_flips() {
+        let mut png = Cursor::new(Vec::new());
+        DynamicImage::ImageRgba8(image(2, 2))
+            .write_to(&mut png, ImageOutputFormat::Png)
+            .unwrap();
+        let transform = |flip_h, flip_v| {
+            let transform =
+                ImageTransform { flip_h, flip_v, ..Default::default() };
+            let bytes = apply_transform(png.get_ref(), &transform).unwrap();
+            image::load_from_memory(&bytes).unwrap().to_rgba8()
+        };
+        assert_eq!(*transform(false, false).get_pixel(0, 0), RED);
+        assert_eq!(*transform(true, false).get_pixel(1, 0), RED);
+        assert_eq!(*transform(false, true).get_pixel(0, 1), RED);
+        assert_eq!(*transform(true, true).get_pixel(1, 1), RED);
+    }
+}
@@ -1,51 +1,38 @@
 use std::fs;
-use std::io;
+use std::io::{self, Read, Seek};
 use std::path::{Path, PathBuf};
 
-use super::errors::{IoError};
-use super::structs::XlsxPath;
+use super::archive::XlsxArchive;
+use super::errors::Error;
 
-pub struct UnzippedPaths {
-    pub unzip_dir: PathBuf,
-    pub media_dir: PathBuf,
-    pub workbook_xml: PathBuf,
-    pub drawing_dir: PathBuf,
-    pub drawing_rels_dir: PathBuf,
-    pub worksheet_rels_dir: PathBuf,
-}
-
-pub fn unzip_xlsx<N>(
-    xlsx_file: &XlsxPath,
+/// unzip every entry of an opened xlsx archive into `temp_dir/file_stem`
+///
+/// returns the dir the archive was unzipped to
+pub fn unzip_xlsx<R, N>(
+    archive: &mut XlsxArchive<R>,
     temp_dir: N,
-) -> Result<Option<UnzippedPaths>, IoError>
+    file_stem: &str,
+) -> Result<PathBuf, Error>
 where
+    R: Read + Seek,
     N: AsRef<Path>,
 {
-    let xlsx_file = xlsx_file.as_pathbuf();
-
-    let file_stem = xlsx_file.file_stem().unwrap().to_str().unwrap();
-
     let unzip_dir = temp_dir.as_ref().join(file_stem);
-    let rename_to_zip = temp_dir.as_ref().join(format!("{file_stem}.zip"));
-    // force to overwrite
-    fs::copy(xlsx_file, &rename_to_zip)?;
-
-    let file = fs::File::open(&rename_to_zip)?;
-    let mut archive = zip::ZipArchive::new(file).unwrap();
+    let archive = archive.zip_mut();
 
     for i in 0..archive.len() {
-        let mut file = archive.by_index(i).unwrap();
+        let mut file = archive.by_index(i)?;
         let outpath = match file.enclosed_name() {
             Some(path) => unzip_dir.join(path),
             None => continue,
         };
 
-        if (*file.name()).ends_with("/") || (*file.name()).ends_with("\\") {
+        if file.name().ends_with('/') || file.name().ends_with('\\') {
             fs::create_dir_all(&outpath)?;
         } else {
             if let Some(p) = outpath.parent() {
                 if !p.exists() {
-                    fs::create_dir_all(&p)?;
+                    fs::create_dir_all(p)?;
                 }
             }
             if outpath.exists() {
@@ -56,29 +43,6 @@ where
             io::copy(&mut file, &mut outfile)?;
         }
     }
-    let xl_dir = unzip_dir.join("xl");
-    fs::remove_file(&rename_to_zip)?;
-    let media_dir = xl_dir.join("media");
-    let workbook_xml = xl_dir.join("workbook.xml");
-    let drawing_dir = xl_dir.join("drawings");
-    let drawing_rels_dir = xl_dir.join("drawings").join("_rels");
-    let worksheet_rels_dir = xl_dir.join("worksheets").join("_rels");
 
-    if media_dir.exists()
-        && workbook_xml.exists()
-        && drawing_dir.exists()
-        && drawing_rels_dir.exists()
-        && worksheet_rels_dir.exists()
-    {
-        Ok(Some(UnzippedPaths {
-            unzip_dir,
-            media_dir,
-            workbook_xml,
-            drawing_dir,
-            drawing_rels_dir,
-            worksheet_rels_dir,
-        }))
-    } else {
-        Ok(None)
-    }
+    Ok(unzip_dir)
 }